@@ -0,0 +1,307 @@
+/*
+    Copyright (C) 2016  Erik Beran
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+// Distance from every stack of a given size to the nearest complete stack.
+//
+// Relabeling colors or turning the stack around doesn't change how many
+// flips it needs (a flip of the reversed stack is the mirrored flip), so the
+// search runs over canonical forms only and multiplies back up for the raw
+// stack counts. Every flip is its own inverse, so searching backward from
+// the complete stacks is the same as searching forward with flip.
+
+use logic::*;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const HEADER : &str = "# sortgame distances";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Distances {
+    pub count : i32,
+    pub type_count : i32,
+    // Indexed by distance: canonical classes / raw stacks that need exactly
+    // that many flips
+    pub classes : Vec<u64>,
+    pub stacks : Vec<u64>,
+}
+
+impl Distances {
+    // Breadth-first from every complete stack with `count` slices and all
+    // `type_count` colors present. The space is about type_count^count / (2 *
+    // type_count!) classes, so keep to small configurations.
+    pub fn compute(count : i32, type_count : i32) -> Result<Distances, SliceStackError> {
+        if count as usize > MAX_SLICES {
+            return Err(SliceStackError::TooManySlices { count:count as usize,
+                capacity:MAX_SLICES });
+        }
+        let mut distance : HashMap<SliceStack, u8> = HashMap::new();
+        let mut frontier : Vec<SliceStack> = Vec::new();
+        for s in complete_stacks(count, type_count)? {
+            let c = s.canonical();
+            if let Entry::Vacant(e) = distance.entry(c) {
+                e.insert(0);
+                frontier.push(c);
+            }
+        }
+
+        let mut d : u8 = 0;
+        while !frontier.is_empty() {
+            d += 1;
+            let mut next_frontier = Vec::new();
+            for s in frontier.iter() {
+                for mv in Move::all(s.count()) {
+                    let mut next = *s;
+                    mv.apply(&mut next);
+                    let c = next.canonical();
+                    if let Entry::Vacant(e) = distance.entry(c) {
+                        e.insert(d);
+                        next_frontier.push(c);
+                    }
+                }
+            }
+            frontier = next_frontier;
+        }
+
+        let mut result = Distances { count, type_count, classes:Vec::new(), stacks:Vec::new() };
+        let labelings = factorial(type_count);
+        for (s, d) in distance.iter() {
+            let d = *d as usize;
+            if result.classes.len() <= d {
+                result.classes.resize(d + 1, 0);
+                result.stacks.resize(d + 1, 0);
+            }
+            result.classes[d] += 1;
+            result.stacks[d] += labelings * orientations(s);
+        }
+        return Ok(result);
+    }
+
+    // God's number: the most flips any stack of this size needs. None when
+    // there are no such stacks (more colors than slices).
+    pub fn max_distance(&self) -> Option<usize> {
+        if self.classes.is_empty() {
+            return None;
+        }
+        return Some(self.classes.len() - 1);
+    }
+
+    pub fn total_classes(&self) -> u64 { self.classes.iter().sum() }
+
+    pub fn total_stacks(&self) -> u64 { self.stacks.iter().sum() }
+
+    // Plain text: a header line, the configuration, then one
+    // "distance classes stacks" line per distance
+    pub fn write_to<W : Write>(&self, w : &mut W) -> io::Result<()> {
+        writeln!(w, "{}", HEADER)?;
+        writeln!(w, "count {}", self.count)?;
+        writeln!(w, "type_count {}", self.type_count)?;
+        for d in 0..self.classes.len() {
+            writeln!(w, "{} {} {}", d, self.classes[d], self.stacks[d])?;
+        }
+        return Ok(());
+    }
+
+    pub fn read_from<R : Read>(r : R) -> io::Result<Distances> {
+        let mut lines = BufReader::new(r).lines();
+        if lines.next().transpose()?.as_deref() != Some(HEADER) {
+            return Err(invalid_data("missing header".to_string()));
+        }
+        let count = read_field(lines.next().transpose()?, "count")?;
+        let type_count = read_field(lines.next().transpose()?, "type_count")?;
+        let mut result = Distances { count, type_count, classes:Vec::new(), stacks:Vec::new() };
+        for line in lines {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let fields : Vec<u64> = line.split_whitespace().map(|f| f.parse::<u64>())
+                .collect::<Result<_, _>>()
+                .map_err(|_| invalid_data(format!("bad distance line {:?}", line)))?;
+            if fields.len() != 3 || fields[0] != result.classes.len() as u64 {
+                return Err(invalid_data(format!("bad distance line {:?}", line)));
+            }
+            result.classes.push(fields[1]);
+            result.stacks.push(fields[2]);
+        }
+        return Ok(result);
+    }
+
+    pub fn save<P : AsRef<Path>>(&self, path : P) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write_to(&mut w)?;
+        return w.flush();
+    }
+
+    pub fn load<P : AsRef<Path>>(path : P) -> io::Result<Distances> {
+        return Distances::read_from(File::open(path)?);
+    }
+
+    // Reuses distances-<count>-<type_count>.txt in `dir` if it is there,
+    // otherwise computes and saves it
+    pub fn load_or_compute<P : AsRef<Path>>(dir : P, count : i32, type_count : i32)
+        -> io::Result<Distances> {
+        let path = dir.as_ref().join(format!("distances-{}-{}.txt", count, type_count));
+        if path.exists() {
+            let d = Distances::load(&path)?;
+            if d.count != count || d.type_count != type_count {
+                return Err(invalid_data(format!("{} is for {} slices, {} colors",
+                                                path.display(), d.count, d.type_count)));
+            }
+            return Ok(d);
+        }
+        let d = Distances::compute(count, type_count)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        d.save(&path)?;
+        return Ok(d);
+    }
+}
+
+// Every complete stack: type_count runs, one per color, whose lengths add up
+// to count. Colors appear in order 0, 1, ...; the other labelings share the
+// canonical form.
+fn complete_stacks(count : i32, type_count : i32) -> Result<Vec<SliceStack>, SliceStackError> {
+    let mut stacks = Vec::new();
+    let mut slices = Vec::new();
+    add_runs(&mut slices, count, 0, type_count, &mut stacks)?;
+    return Ok(stacks);
+}
+
+fn add_runs(slices : &mut Vec<SliceType>, left : i32, color : i32, type_count : i32,
+            stacks : &mut Vec<SliceStack>) -> Result<(), SliceStackError> {
+    if color == type_count {
+        if left == 0 {
+            stacks.push(SliceStack::from_slices(slices, type_count)?);
+        }
+        return Ok(());
+    }
+    // Leave at least one slice for each remaining color
+    let remaining_colors = type_count - color - 1;
+    for len in 1..=(left - remaining_colors) {
+        for _ in 0..len {
+            slices.push(color as SliceType);
+        }
+        add_runs(slices, left - len, color + 1, type_count, stacks)?;
+        slices.truncate(slices.len() - len as usize);
+    }
+    return Ok(());
+}
+
+fn factorial(n : i32) -> u64 {
+    return (1..=n as u64).product();
+}
+
+// Raw stacks per labeling of a canonical class: 1 if turning it around
+// gives the same stack up to labels, otherwise 2
+fn orientations(canonical : &SliceStack) -> u64 {
+    if canonical.reversed().relabeled() == *canonical {
+        return 1;
+    }
+    return 2;
+}
+
+fn read_field(line : Option<String>, name : &str) -> io::Result<i32> {
+    let line = line.unwrap_or_default();
+    let mut fields = line.split_whitespace();
+    if fields.next() == Some(name) {
+        if let Some(Ok(v)) = fields.next().map(|f| f.parse::<i32>()) {
+            return Ok(v);
+        }
+    }
+    return Err(invalid_data(format!("expected \"{} <number>\", found {:?}", name, line)));
+}
+
+fn invalid_data(message : String) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, message);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solver::*;
+
+    // Stirling numbers of the second kind times k!: strings of length n
+    // using all k colors
+    fn surjections(n : i32, k : i32) -> u64 {
+        let mut total : i64 = 0;
+        for j in 0..=k {
+            let sign = if (k - j) % 2 == 0 { 1 } else { -1 };
+            let binom = (factorial(k) / (factorial(j) * factorial(k - j))) as i64;
+            total += sign * binom * (j as i64).pow(n as u32);
+        }
+        return total as u64;
+    }
+
+    #[test]
+    fn distances_small_test() {
+        let d = Distances::compute(3, 2).unwrap();
+        // AAB, ABB (and relabelings) are complete; ABA needs one flip
+        assert_eq!(vec![1, 1], d.classes);
+        assert_eq!(vec![4, 2], d.stacks);
+        assert_eq!(Some(1), d.max_distance());
+
+        assert_eq!(None, Distances::compute(2, 3).unwrap().max_distance());
+        assert_eq!(SliceStackError::TooManySlices { count:17, capacity:MAX_SLICES },
+                   Distances::compute(17, 2).unwrap_err());
+    }
+
+    #[test]
+    fn distances_match_solver_test() {
+        let ida = IdaSolver::new();
+        for &(count, type_count) in [(6, 2), (7, 3), (7, 4)].iter() {
+            let d = Distances::compute(count, type_count).unwrap();
+            assert_eq!(surjections(count, type_count), d.total_stacks());
+            // Recount from per stack optimal solutions
+            let mut stacks = vec![0u64; d.stacks.len()];
+            let total = (type_count as u64).pow(count as u32);
+            for mut n in 0..total {
+                let mut slices = Vec::new();
+                for _ in 0..count {
+                    slices.push((n % type_count as u64) as SliceType);
+                    n /= type_count as u64;
+                }
+                if let Ok(s) = SliceStack::from_slices(&slices, type_count) {
+                    stacks[ida.solve(&s).len()] += 1;
+                }
+            }
+            assert_eq!(d.stacks, stacks);
+        }
+    }
+
+    #[test]
+    fn distances_file_test() {
+        let d = Distances::compute(7, 3).unwrap();
+        let mut text = Vec::new();
+        d.write_to(&mut text).unwrap();
+        assert_eq!(d, Distances::read_from(&text[..]).unwrap());
+
+        let bad = b"# sortgame distances\ncount 7\ntype_count 3\n0 1\n";
+        assert_eq!(io::ErrorKind::InvalidData, Distances::read_from(&bad[..]).unwrap_err().kind());
+        assert!(Distances::read_from(&b"count 7\n"[..]).is_err());
+
+        let dir = std::env::temp_dir().join(format!("sortgame-distances-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let computed = Distances::load_or_compute(&dir, 7, 3).unwrap();
+        assert!(dir.join("distances-7-3.txt").exists());
+        assert_eq!(computed, Distances::load_or_compute(&dir, 7, 3).unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+/* vim: set ts=4 sts=4 sw=4 et : */
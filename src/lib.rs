@@ -1,4 +1,11 @@
+// Explicit `return` statements are used throughout the crate
+#![allow(clippy::needless_return)]
+
+extern crate rayon;
 
 pub mod prng;
 pub mod logic;
+pub mod session;
+pub mod solver;
+pub mod analysis;
 
@@ -0,0 +1,162 @@
+/*
+    Copyright (C) 2016  Erik Beran
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+// Comparison, hashing and canonical forms. Only the first `count` slices
+// (and their payloads) take part; the unused tail of the arrays and
+// type_count are ignored.
+
+use super::{SliceStackN, SliceType};
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+
+const COLOR_RANGE : usize = SliceType::MAX as usize + 1;
+
+impl<const N : usize, T : PartialEq> PartialEq for SliceStackN<N, T> {
+    fn eq(&self, other : &SliceStackN<N, T>) -> bool {
+        return self.slices() == other.slices() && self.objects() == other.objects();
+    }
+}
+
+impl<const N : usize, T : Eq> Eq for SliceStackN<N, T> {}
+
+impl<const N : usize, T : Hash> Hash for SliceStackN<N, T> {
+    fn hash<H : Hasher>(&self, state : &mut H) {
+        self.slices().hash(state);
+        self.objects().hash(state);
+    }
+}
+
+// Lexicographic on the slice colors, then on the payloads
+impl<const N : usize, T : PartialOrd> PartialOrd for SliceStackN<N, T> {
+    fn partial_cmp(&self, other : &SliceStackN<N, T>) -> Option<Ordering> {
+        match self.slices().cmp(other.slices()) {
+            Ordering::Equal => self.objects().partial_cmp(other.objects()),
+            ord => Some(ord)
+        }
+    }
+}
+
+impl<const N : usize, T : Ord> Ord for SliceStackN<N, T> {
+    fn cmp(&self, other : &SliceStackN<N, T>) -> Ordering {
+        return self.slices().cmp(other.slices())
+            .then_with(|| self.objects().cmp(other.objects()));
+    }
+}
+
+impl<const N : usize, T : Clone> SliceStackN<N, T> {
+    // Colors renumbered 0, 1, 2, ... in order of first appearance from the
+    // left edge. Relabeling colors doesn't change the puzzle.
+    pub fn relabeled(&self) -> SliceStackN<N, T> {
+        let mut s = self.clone();
+        relabel(&mut s.slice_type[0..self.count as usize]);
+        return s;
+    }
+
+    // The whole stack turned around; also the same puzzle
+    pub fn reversed(&self) -> SliceStackN<N, T> {
+        let mut s = self.clone();
+        s.slice_type[0..self.count as usize].reverse();
+        s.slice_object[0..self.count as usize].reverse();
+        return s;
+    }
+
+    // Representative shared by every stack that differs from this one only by
+    // color labels or by reversal: the smaller (by slice colors) of the
+    // relabeled stack and the relabeled reverse.
+    pub fn canonical(&self) -> SliceStackN<N, T> {
+        let forward = self.relabeled();
+        let backward = self.reversed().relabeled();
+        if backward.slices() < forward.slices() {
+            return backward;
+        }
+        return forward;
+    }
+
+    pub fn is_canonical(&self) -> bool {
+        return self.canonical().slices() == self.slices();
+    }
+}
+
+fn relabel(slices : &mut [SliceType]) {
+    // Colors are at most SliceType::MAX, so new labels always fit
+    let mut label : [Option<SliceType>; COLOR_RANGE] = [None; COLOR_RANGE];
+    let mut next : usize = 0;
+    for t in slices.iter_mut() {
+        let l = &mut label[*t as usize];
+        if l.is_none() {
+            *l = Some(next as SliceType);
+            next += 1;
+        }
+        *t = l.unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use logic::testing::*;
+    use logic::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn eq_ignores_unused_tail_test() {
+        let mut a = stack("ABBC");
+        let mut b = a;
+        a.slice_type[7] = 5;
+        b.slice_type[9] = 3;
+        assert!(a == b);
+        let mut set = HashSet::new();
+        set.insert(a);
+        assert!(set.contains(&b));
+        assert!(!set.contains(&stack("ABB")));
+        assert!(stack("ABB") < a);
+        assert!(stack("AC") > a);
+    }
+
+    #[test]
+    fn canonical_test() {
+        let s = stack("DDBAB");
+        assert_eq!(&[0, 0, 1, 2, 1], s.relabeled().slices());
+        assert_eq!(&[1, 0, 1, 3, 3], s.reversed().slices());
+        // The reverse relabels to 0,1,0,2,2 which is larger
+        assert_eq!(&[0, 0, 1, 2, 1], s.canonical().slices());
+        assert!(!s.is_canonical());
+        assert!(s.canonical().is_canonical());
+
+        // Every relabeling and reversal shares the canonical form
+        let variants = [stack("CCABA"), stack("AFAHH"),
+                        stack("BABDD"), stack("9, 8, 9, 200, 200")];
+        let mut set = HashSet::new();
+        set.insert(s.canonical());
+        for v in variants.iter() {
+            assert!(v.canonical() == s.canonical());
+            set.insert(v.canonical());
+        }
+        assert_eq!(1, set.len());
+        assert!(stack("ABCAB").canonical() != s.canonical());
+    }
+
+    #[test]
+    fn canonical_payload_test() {
+        let s : SliceStack<char> = stack("BAA").with_objects("abc".chars());
+        let c = s.canonical();
+        assert_eq!(&[0, 0, 1], c.slices());
+        assert_eq!(&['c', 'b', 'a'], c.objects());
+        assert!(c != s.without_objects().canonical().with_objects("xyz".chars()));
+    }
+}
+
+/* vim: set ts=4 sts=4 sw=4 et : */
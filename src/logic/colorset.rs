@@ -0,0 +1,109 @@
+/*
+    Copyright (C) 2016  Erik Beran
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use super::SliceType;
+
+const WORD_BITS : usize = 64;
+
+// Bitset of slice colors. The first 64 colors live inline so the common case
+// never allocates; higher color ids grow the set on demand.
+#[derive(Clone, Default, Debug)]
+pub struct ColorSet {
+    low : u64,
+    high : Vec<u64>
+}
+
+impl ColorSet {
+    pub fn new() -> ColorSet { ColorSet { low:0, high:Vec::new() } }
+
+    // Returns true if the color wasn't in the set yet
+    pub fn insert(&mut self, color : SliceType) -> bool {
+        let c = color as usize;
+        let bit : u64 = 1 << (c % WORD_BITS);
+        let word : &mut u64 = if c < WORD_BITS {
+            &mut self.low
+        } else {
+            let w = c / WORD_BITS - 1;
+            if w >= self.high.len() {
+                self.high.resize(w + 1, 0);
+            }
+            &mut self.high[w]
+        };
+        let added = (*word & bit) == 0;
+        *word |= bit;
+        return added;
+    }
+
+    pub fn contains(&self, color : SliceType) -> bool {
+        let c = color as usize;
+        let bit : u64 = 1 << (c % WORD_BITS);
+        if c < WORD_BITS {
+            return (self.low & bit) != 0;
+        }
+        return match self.high.get(c / WORD_BITS - 1) {
+            Some(word) => (*word & bit) != 0,
+            None => false
+        };
+    }
+
+    // Number of colors in the set
+    pub fn len(&self) -> usize {
+        let mut n = self.low.count_ones() as usize;
+        for word in self.high.iter() {
+            n += word.count_ones() as usize;
+        }
+        return n;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.len() == 0;
+    }
+
+    // Keeps any grown storage around so the set can be reused cheaply
+    pub fn clear(&mut self) {
+        self.low = 0;
+        for word in self.high.iter_mut() {
+            *word = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colorset_insert_contains_test() {
+        let mut set = ColorSet::new();
+        assert!(set.is_empty());
+        for c in [0, 31, 32, 63, 64, 200, 255].iter() {
+            assert!(!set.contains(*c));
+            assert!(set.insert(*c));
+            assert!(set.contains(*c));
+            assert!(!set.insert(*c));
+        }
+        assert_eq!(7, set.len());
+        assert!(!set.contains(1));
+        assert!(!set.contains(65));
+        assert!(!set.contains(254));
+        set.clear();
+        assert!(set.is_empty());
+        assert!(!set.contains(200));
+    }
+}
+
+/* vim: set ts=4 sts=4 sw=4 et : */
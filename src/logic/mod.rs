@@ -19,44 +19,208 @@
 // experimental code that isn't always used. (experimenting with performance).
 #![allow(dead_code)]
 
+// Default stack capacity; SliceStackN<N> can be used for other capacities
 pub const MAX_SLICES : usize = 16;
 
+mod canonical;
+mod colorset;
+mod moves;
+mod notation;
+mod rank;
+#[cfg(test)]
+pub mod testing;
+
+pub use self::colorset::ColorSet;
+pub use self::moves::{Move, ParseMoveError, Side};
+pub use self::notation::ParseStackError;
+pub use self::rank::{relabeled_state_count, state_count};
 use prng::*;
+use std::error::Error;
+use std::fmt;
 use std::ptr::*;
 
 // New type so we can experiment with performance between 8,16,32 bits
-type SliceType = u8;
+pub type SliceType = u8;
 
+// Stack holding up to N slices; only the first `count` entries are used.
+// Each slice carries a payload of type T (e.g. a sprite handle) that moves
+// with its color through every flip, like slice_object in logic.h. With the
+// default T = () the payload array takes no space and swapping it is free.
 #[derive(Copy,Clone)]
-pub struct SliceStack {
+pub struct SliceStackN<const N : usize, T = ()> {
     count : i32,
     type_count : i32,
-    slice_type : [SliceType; MAX_SLICES]
+    slice_type : [SliceType; N],
+    slice_object : [T; N]
+}
+
+// The original 16 slice stack
+pub type SliceStack<T = ()> = SliceStackN<MAX_SLICES, T>;
+
+// Reasons SliceStackN::from_slices or unrank can reject a layout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SliceStackError {
+    // More slices than the stack can hold
+    TooManySlices { count : usize, capacity : usize },
+    // A slice uses a color id >= type_count
+    ColorOutOfRange { index : usize, color : SliceType, type_count : i32 },
+    // type_count doesn't match the number of distinct colors present
+    TypeCountMismatch { type_count : i32, colors_present : usize },
+    // unrank given a rank past the number of layouts
+    RankOutOfRange { rank : u64, states : u64 },
+    // More layouts of this size than a u64 can number
+    TooManyStates { count : i32, type_count : i32 },
+}
+
+impl fmt::Display for SliceStackError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SliceStackError::TooManySlices { count, capacity } =>
+                write!(f, "{} slices given but the stack holds at most {}", count, capacity),
+            SliceStackError::ColorOutOfRange { index, color, type_count } =>
+                write!(f, "slice {} has color {} but only {} colors are allowed",
+                       index, color, type_count),
+            SliceStackError::TypeCountMismatch { type_count, colors_present } =>
+                write!(f, "type count is {} but {} distinct colors are present",
+                       type_count, colors_present),
+            SliceStackError::RankOutOfRange { rank, states } =>
+                write!(f, "rank {} is out of range; there are {} layouts", rank, states),
+            SliceStackError::TooManyStates { count, type_count } =>
+                write!(f, "{} slices of {} colors have too many layouts to number",
+                       count, type_count),
+        }
+    }
 }
 
-impl SliceStack {
-    pub fn new() -> SliceStack { SliceStack { count:0, type_count:0, slice_type:[0;MAX_SLICES] } }
+impl Error for SliceStackError {}
+
+impl<const N : usize, T : Default> Default for SliceStackN<N, T> {
+    fn default() -> SliceStackN<N, T> {
+        SliceStackN { count:0, type_count:0, slice_type:[0;N],
+            slice_object:std::array::from_fn(|_| T::default()) }
+    }
+}
 
-    pub fn init(&mut self, count : i32, type_count : i32) {
+// Constructors live on the payload-free stack so SliceStack::new() doesn't
+// need a type annotation; use Default or with_objects for payloads.
+impl<const N : usize> SliceStackN<N> {
+    pub fn new() -> SliceStackN<N> {
+        SliceStackN { count:0, type_count:0, slice_type:[0;N], slice_object:[();N] }
+    }
+
+    // Builds a stack with exactly the given layout. Every color must be below
+    // type_count and all type_count colors must be present, which is what
+    // iscomplete2 relies on.
+    pub fn from_slices(slices : &[SliceType], type_count : i32)
+        -> Result<SliceStackN<N>, SliceStackError> {
+        let s = SliceStackN::from_slices_with_gaps(slices, type_count)?;
+        let present = s.colors();
+        if present.len() as i32 != type_count {
+            return Err(SliceStackError::TypeCountMismatch { type_count,
+                colors_present:present.len() });
+        }
+        return Ok(s);
+    }
+
+    // from_slices without the every-color-present rule, for layouts like the
+    // ones init_with deals that may skip colors
+    fn from_slices_with_gaps(slices : &[SliceType], type_count : i32)
+        -> Result<SliceStackN<N>, SliceStackError> {
+        if slices.len() > N {
+            return Err(SliceStackError::TooManySlices { count:slices.len(), capacity:N });
+        }
+        for (i, t) in slices.iter().enumerate() {
+            if (*t as i32) >= type_count {
+                return Err(SliceStackError::ColorOutOfRange { index:i, color:*t, type_count });
+            }
+        }
+        let mut s = SliceStackN::new();
+        s.count = slices.len() as i32;
+        s.type_count = type_count;
+        s.slice_type[0..slices.len()].copy_from_slice(slices);
+        return Ok(s);
+    }
+}
+
+impl<const N : usize, T> SliceStackN<N, T> {
+    pub const CAPACITY : usize = N;
+
+    pub fn capacity(&self) -> usize { N }
+
+    // Number of slices in use
+    pub fn count(&self) -> i32 { self.count }
+
+    pub fn type_count(&self) -> i32 { self.type_count }
+
+    // The slices in use, bottom (left edge) first
+    pub fn slices(&self) -> &[SliceType] {
+        return &self.slice_type[0..self.count as usize];
+    }
+
+    pub fn slice(&self, index : usize) -> Option<SliceType> {
+        return self.slices().get(index).cloned();
+    }
+
+    // Payloads of the slices in use, in the same order as slices()
+    pub fn objects(&self) -> &[T] {
+        return &self.slice_object[0..self.count as usize];
+    }
+
+    pub fn objects_mut(&mut self) -> &mut [T] {
+        return &mut self.slice_object[0..self.count as usize];
+    }
+
+    pub fn object(&self, index : usize) -> Option<&T> {
+        return self.objects().get(index);
+    }
+
+    pub fn object_mut(&mut self, index : usize) -> Option<&mut T> {
+        return self.objects_mut().get_mut(index);
+    }
+
+    // Same layout with new payloads, taken in slice order. Slices without a
+    // payload from `objects` get U::default().
+    pub fn with_objects<U, I>(&self, objects : I) -> SliceStackN<N, U>
+        where U : Default, I : IntoIterator<Item = U> {
+        let mut s : SliceStackN<N, U> = SliceStackN { count:self.count,
+            type_count:self.type_count, slice_type:self.slice_type, ..Default::default() };
+        for (o, object) in s.slice_object[0..self.count as usize].iter_mut().zip(objects) {
+            *o = object;
+        }
+        return s;
+    }
+
+    // Same layout with the payloads dropped; what the solvers work on
+    pub fn without_objects(&self) -> SliceStackN<N> {
+        return SliceStackN { count:self.count, type_count:self.type_count,
+            slice_type:self.slice_type, slice_object:[();N] };
+    }
+
+    // Random layout drawn from rng. The same generator state always gives
+    // the same stack, so seeding one generator per stack (or per thread)
+    // makes generation reproducible regardless of scheduling.
+    pub fn init_with<R : Prng + ?Sized>(&mut self, rng : &mut R, count : i32, type_count : i32) {
         self.count = count;
         self.type_count = type_count;
         for i in 0..count as usize {
-            self.slice_type[i] = fake_rand(0, type_count-1) as SliceType;
+            self.slice_type[i] = rng.int_minmax(0, type_count-1) as SliceType;
         }
     }
 
-    pub fn init_unsafe(&mut self, count : i32, type_count : i32) {
+    pub fn init_with_unsafe<R : Prng + ?Sized>(&mut self, rng : &mut R, count : i32,
+                                               type_count : i32) {
         self.count = count;
         self.type_count = type_count;
         let mut i : usize = 0;
         while i < count as usize {
-            unsafe { *self.slice_type.get_unchecked_mut(i) = fake_rand(0,type_count-1) as SliceType };
+            unsafe { *self.slice_type.get_unchecked_mut(i) = rng.int_minmax(0,type_count-1) as SliceType };
             i += 1;
         }
     }
 
     fn swapslices(&mut self, i0 : i32, i1 : i32) {
         self.slice_type.swap(i0 as usize, i1 as usize);
+        self.slice_object.swap(i0 as usize, i1 as usize);
     }
 
     fn swapslices_unsafe(&mut self, i0 : i32, i1 : i32) {
@@ -65,6 +229,8 @@ impl SliceStack {
         unsafe {
             swap(self.slice_type.get_unchecked_mut(i0 as usize),
                 self.slice_type.get_unchecked_mut(i1 as usize));
+            swap(self.slice_object.get_unchecked_mut(i0 as usize),
+                self.slice_object.get_unchecked_mut(i1 as usize));
         }
     }
 
@@ -114,20 +280,24 @@ impl SliceStack {
         if direction > 0 {
             let slice : &mut [SliceType] = &mut self.slice_type[(index as usize)..(self.count as usize)];
             slice.reverse();
+            self.slice_object[(index as usize)..(self.count as usize)].reverse();
         } else {
             let slice : &mut [SliceType] = &mut self.slice_type[0..((index+1) as usize)];
             slice.reverse();
+            self.slice_object[0..((index+1) as usize)].reverse();
         }
     }
 
     // Fragmentation value of stack; minimum is different slice types
     // i.e. if fragmentation - slice_types == 0, it's completed
     pub fn fragmentation(&self) -> i32 {
-        let mut last_type : SliceType = SliceType::max_value();
+        let mut last_type : SliceType = SliceType::MAX;
         let mut frag : i32 = 0;
         let mut i : usize = 0;
         while i < (self.count as usize) {
-            if last_type != self.slice_type[i] {
+            // last_type is only a placeholder for the first slice; any color
+            // (including SliceType::MAX) starts a new section there
+            if i == 0 || last_type != self.slice_type[i] {
                 frag += 1;
             }
             last_type = self.slice_type[i];
@@ -137,12 +307,12 @@ impl SliceStack {
     }
 
     pub fn fragmentation_unsafe(&self) -> i32 {
-        let mut last_type : SliceType = SliceType::max_value();
+        let mut last_type : SliceType = SliceType::MAX;
         let mut frag : i32 = 0;
         let mut i : usize = 0;
         while i < (self.count as usize) {
             let t = unsafe { *self.slice_type.get_unchecked(i) };
-            if last_type != t {
+            if i == 0 || last_type != t {
                 frag += 1;
             }
             last_type = t;
@@ -163,86 +333,90 @@ impl SliceStack {
 
     // Returns bool, true on stack/level is complete
     pub fn iscomplete(&self) -> bool {
-        let mut used_type_flags : u32 = 0;
-        let mut last_type : SliceType = SliceType::max_value();
-        let mut type_flag : u32;
+        let mut used_types = ColorSet::new();
+        let mut last_type : SliceType = SliceType::MAX;
         for t in self.slice_type[0..self.count as usize].iter() {
-            type_flag = 1 << *t;
-            if ((used_type_flags & type_flag) != 0) && (last_type != *t) {
+            // The first slice is always newly inserted, so the placeholder
+            // last_type is never compared
+            if !used_types.insert(*t) && (last_type != *t) {
                 return false;
             }
-            used_type_flags |= type_flag;
             last_type = *t;
         }
         return true;
     }
 
     pub fn iscomplete_unsafe(&self) -> bool {
-        let mut used_type_flags : u32 = 0;
-        let mut last_type : u32 = 0x000000FF;
+        let mut used_types = ColorSet::new();
+        let mut last_type : SliceType = SliceType::MAX;
         let mut i : i32 = 0;
-        let mut type_flag : u32;
         while i < self.count {
-            let t = unsafe { *self.slice_type.get_unchecked(i as usize) as u32 };
-            type_flag = 1 << t;
-            if ((used_type_flags & type_flag) != 0) && (last_type != t) {
+            let t = unsafe { *self.slice_type.get_unchecked(i as usize) };
+            if !used_types.insert(t) && (last_type != t) {
                 return false;
             }
-            used_type_flags |= type_flag;
             last_type = t;
             i += 1;
         }
         return true;
     }
 
+    // Set of colors present in the stack
+    pub fn colors(&self) -> ColorSet {
+        let mut set = ColorSet::new();
+        for t in self.slice_type[0..self.count as usize].iter() {
+            set.insert(*t);
+        }
+        return set;
+    }
+
     // This only works if type_count is correct (i.e. if you tally up the
     // different colors in the color_type array, it will match type_count)
     pub fn iscomplete2(&self) -> bool {
         return (self.fragmentation2() - self.type_count) <= 0;
     }
 
-    // Success: Valid index and direction
-    // Failure: -1 (Cannot find valid move)
-    pub fn find_single_joining_move(&self, dir : &mut i32)
-        -> i32 {
-        let mut found_diff_type : bool;
-        // left edge
-        {
-            found_diff_type = false;
-            let outer_type = self.slice_type[0];
-            for (i,t) in self.slice_type[0..(self.count as usize)].iter().enumerate() {
-                if *t == outer_type {
-                    if found_diff_type {
-                        *dir = -1;
-                        return i as i32;
+    // Success: Some(move) that joins an edge section with its color
+    // Failure: None (Cannot find valid move)
+    pub fn find_single_joining_move(&self) -> Option<Move> {
+        return self.find_single_joining_move_on(Side::Left)
+            // We haven't found a matching type, try from the other end and direction
+            .or_else(|| self.find_single_joining_move_on(Side::Right));
+    }
+
+    // find_single_joining_move, looking at one edge only
+    pub fn find_single_joining_move_on(&self, side : Side) -> Option<Move> {
+        let mut found_diff_type = false;
+        match side {
+            Side::Left => {
+                let outer_type = self.slice_type[0];
+                for (i,t) in self.slice_type[0..(self.count as usize)].iter().enumerate() {
+                    if *t == outer_type {
+                        if found_diff_type {
+                            return Some(Move::beyond(i as i32, Side::Left));
+                        }
+                    } else {
+                        found_diff_type = true;
                     }
-                } else {
-                    found_diff_type = true;
                 }
             }
-        }
-
-        // We haven't found a matching type, try from the other end and direction
-        // right edge
-        {
-            found_diff_type = false;
-            let outer_type = self.slice_type[(self.count - 1) as usize];
-            for (i,t) in self.slice_type[1..(self.count as usize)].iter().enumerate().rev() {
-                if *t == outer_type {
-                    if found_diff_type {
-                        *dir = 1;
-                        return (i as i32) + 1;
+            Side::Right => {
+                let outer_type = self.slice_type[(self.count - 1) as usize];
+                for (i,t) in self.slice_type[1..(self.count as usize)].iter().enumerate().rev() {
+                    if *t == outer_type {
+                        if found_diff_type {
+                            return Some(Move::beyond((i as i32) + 1, Side::Right));
+                        }
+                    } else {
+                        found_diff_type = true;
                     }
-                } else {
-                    found_diff_type = true;
                 }
             }
         }
-        return -1;
+        return None;
     }
 
-    pub fn find_single_joining_move_unchecked(&self, dir : &mut i32)
-        -> i32 {
+    pub fn find_single_joining_move_unchecked(&self) -> Option<Move> {
         let mut found_diff_type : bool;
         let mut i : i32;
         // left edge
@@ -254,8 +428,7 @@ impl SliceStack {
                 let t = *self.slice_type.get_unchecked(i as usize);
                 if t == outer_type {
                     if found_diff_type {
-                        *dir = -1;
-                        return i;
+                        return Some(Move::beyond(i, Side::Left));
                     }
                 } else {
                     found_diff_type = true;
@@ -274,8 +447,7 @@ impl SliceStack {
                 let t = *self.slice_type.get_unchecked(i as usize);
                 if t == outer_type {
                     if found_diff_type {
-                        *dir = 1;
-                        return i;
+                        return Some(Move::beyond(i, Side::Right));
                     }
                 } else {
                     found_diff_type = true;
@@ -283,13 +455,12 @@ impl SliceStack {
                 i -= 1;
             }
         }
-        return -1;
+        return None;
     }
 
-    // Success: Valid index and direction
-    // Failure: -1 (Cannot find valid move)
-    pub fn find_single_joining_move_rawptr(&self, dir : &mut i32)
-        -> i32 {
+    // Success: Some(move) that joins an edge section with its color
+    // Failure: None (Cannot find valid move)
+    pub fn find_single_joining_move_rawptr(&self) -> Option<Move> {
         let mut last_index : isize = 0;
         let mut i : isize;
         // left edge
@@ -300,8 +471,7 @@ impl SliceStack {
             while i < self.count as isize {
                 if (*array.offset(i) == left_type)
                     && (*array.offset(i) != *array.offset(last_index)) {
-                    *dir = -1;
-                    return i as i32;
+                    return Some(Move::beyond(i as i32, Side::Left));
                 }
                 last_index = i;
                 i += 1;
@@ -318,23 +488,21 @@ impl SliceStack {
             while i >= 0 {
                 if (*array.offset(i) == right_type)
                     && (*array.offset(i) != *array.offset(last_index)) {
-                    *dir = 1;
-                    return i as i32;
+                    return Some(Move::beyond(i as i32, Side::Right));
                 }
                 last_index = i;
                 i -= 1;
             }
         }
-        return -1;
+        return None;
     }
 
-    // Success: Valid index and direction
-    // Failure: -1 (Cannot find valid move, should not happen)
-    #[allow(unreachable_code)]
-    pub fn find_first_double_move(&self, search_dir : i32, dir : &mut i32)
-        -> i32 {
-        let mut c_index : [u8; MAX_SLICES] = [0xFF; MAX_SLICES];
-        let mut last_type : SliceType = SliceType::max_value();
+    // Success: Some(move) that sets up a joining move
+    // Failure: None (Stack is already complete)
+    pub fn find_first_double_move(&self, search_dir : i32)
+        -> Option<Move> {
+        let mut c_seen = ColorSet::new();
+        let mut last_type : SliceType = SliceType::MAX;
         let mut i : i32;
 
         if search_dir <= 0 {
@@ -342,16 +510,13 @@ impl SliceStack {
             while i < self.count {
                 // Color hasn't been recorded yet
                 let t = self.slice_type[i as usize];
-                let ci : &mut u8 = &mut c_index[t as usize];
-                if *ci == 0xFF {
-                    *ci = i as u8;
+                if c_seen.insert(t) {
                     last_type = t
                 // non-contiguous color found
                 // last_type will have been initialized by first iteration; don't check
-                // for 0xff. If last_type == current_type we don't need to update it
+                // for an unseen color. If last_type == current_type we don't need to update it
                 } else if t != last_type {
-                    *dir = 1;
-                    return i - 1;
+                    return Some(Move::beyond(i - 1, Side::Right));
                 }
                 i += 1;
             }
@@ -360,16 +525,13 @@ impl SliceStack {
             while i > 0 {
                 // Color hasn't been recorded yet
                 let t = self.slice_type[i as usize];
-                let ci : &mut u8 = &mut c_index[t as usize];
-                if *ci == 0xFF {
-                    *ci = i as u8;
+                if c_seen.insert(t) {
                     last_type = t;
                 // non-contiguous color found
                 // last_type will have been initialized by first iteration; don't check
-                // for 0xff. If last_type == current_type we don't need to update it
+                // for an unseen color. If last_type == current_type we don't need to update it
                 } else if t != last_type {
-                    *dir = -1;
-                    return i + 1;
+                    return Some(Move::beyond(i + 1, Side::Left));
                 }
                 i -= 1;
             }
@@ -378,32 +540,27 @@ impl SliceStack {
         // This should only happen when level is complete; As long as there is
         // fragmentation, there will be a way to move one section to the edge
         // to start the double move.
-        unreachable!();
-        return -1;
+        return None;
     }
 
-    #[allow(unreachable_code)]
-    pub fn find_first_double_move_unsafe(&self, search_dir : i32, dir : &mut i32)
-        -> i32 {
-        let mut c_index : [u8; MAX_SLICES] = [0xFF; MAX_SLICES];
-        let mut last_type : SliceType = SliceType::max_value();
+    pub fn find_first_double_move_unsafe(&self, search_dir : i32)
+        -> Option<Move> {
+        let mut c_seen = ColorSet::new();
+        let mut last_type : SliceType = SliceType::MAX;
         let mut i : i32;
 
         if search_dir <= 0 {
             i = 0;
             while i < self.count {
                 let t = unsafe { *self.slice_type.get_unchecked(i as usize) };
-                let ci = unsafe { c_index.get_unchecked_mut(t as usize) };
                 // Color hasn't been recorded yet
-                if *ci == 0xFF {
-                    *ci = i as u8;
+                if c_seen.insert(t) {
                     last_type = t
                 // non-contiguous color found
                 // last_type will have been initialized by first iteration; don't check
-                // for 0xff. If last_type == current_type we don't need to update it
+                // for an unseen color. If last_type == current_type we don't need to update it
                 } else if t != last_type {
-                    *dir = 1;
-                    return i - 1;
+                    return Some(Move::beyond(i - 1, Side::Right));
                 }
                 i += 1;
             }
@@ -411,17 +568,14 @@ impl SliceStack {
             i = self.count - 1;
             while i > 0 {
                 let t = unsafe { *self.slice_type.get_unchecked(i as usize) };
-                let ci = unsafe { c_index.get_unchecked_mut(t as usize) };
                 // Color hasn't been recorded yet
-                if *ci == 0xFF {
-                    *ci = i as u8;
+                if c_seen.insert(t) {
                     last_type = t;
                 // non-contiguous color found
                 // last_type will have been initialized by first iteration; don't check
-                // for 0xff. If last_type == current_type we don't need to update it
+                // for an unseen color. If last_type == current_type we don't need to update it
                 } else if t != last_type {
-                    *dir = -1;
-                    return i + 1;
+                    return Some(Move::beyond(i + 1, Side::Left));
                 }
                 i -= 1;
             }
@@ -430,22 +584,13 @@ impl SliceStack {
         // This should only happen when level is complete; As long as there is
         // fragmentation, there will be a way to move one section to the edge
         // to start the double move.
-        unreachable!();
-        return -1;
+        return None;
     }
 }
 
-/* START PRNG HELPERS */
-// global; not thread-safe etc
-static mut L_CTX : PrngCtxGaloisLsfw = PrngCtxGaloisLsfw {value:34};
-fn fake_rand(min : i32, max : i32) -> i32 {
-    unsafe {
-        return prng_galois_lsfw_int_minmax(&mut L_CTX, min, max);
-    }
-}
-/* END PRNG HELPERS */
-
+// The original tests predate clippy; leave them as written
 #[cfg(test)]
+#[allow(clippy::unnecessary_cast, clippy::needless_range_loop, clippy::useless_vec)]
 mod tests {
     use super::*;
 
@@ -492,19 +637,112 @@ mod tests {
         let mut ss : SliceStack = SliceStack{
             count : 0,
             type_count : 0,
-            slice_type : [0; MAX_SLICES]
+            slice_type : [0; MAX_SLICES],
+            slice_object : [(); MAX_SLICES]
         };
-        ss.init(4, 2);
+        ss.init_with(&mut PrngCtxGaloisLsfw::new(34), 4, 2);
         assert_eq!(4, ss.count);
         assert_eq!(2, ss.type_count);
     }
 
+    #[test]
+    fn init_reproducible_test() {
+        use std::thread;
+
+        // Each thread seeds its own generator; results can't depend on
+        // which thread runs first
+        let generate = |seed : u32| {
+            thread::spawn(move || {
+                let mut rng = PrngCtxGaloisLsfw::new(seed);
+                let mut ss = vec![SliceStack::new(); 256];
+                for s in ss.iter_mut() {
+                    s.init_with(&mut rng, MAX_SLICES as i32, 8);
+                }
+                ss
+            })
+        };
+        let runs : Vec<_> = [7, 7, 7, 8].iter().map(|seed| generate(*seed)).collect();
+        let runs : Vec<Vec<SliceStack>> = runs.into_iter().map(|h| h.join().unwrap()).collect();
+        assert!(runs[0] == runs[1]);
+        assert!(runs[0] == runs[2]);
+        assert!(runs[0] != runs[3]);
+
+        let mut a = SliceStack::new();
+        let mut b = SliceStack::new();
+        a.init_with(&mut PrngCtxGaloisLsfw::new(99), 12, 5);
+        b.init_with_unsafe(&mut PrngCtxGaloisLsfw::new(99), 12, 5);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn from_slices_test() {
+        let ss = SliceStack::from_slices(&[0, 2, 1, 1], 3).unwrap();
+        assert_eq!(4, ss.count());
+        assert_eq!(3, ss.type_count());
+        assert_eq!(&[0, 2, 1, 1], ss.slices());
+        assert_eq!(Some(2), ss.slice(1));
+        assert_eq!(None, ss.slice(4));
+        assert!(ss.iscomplete());
+
+        let empty = SliceStack::from_slices(&[], 0).unwrap();
+        assert_eq!(0, empty.count());
+        assert!(empty.iscomplete());
+
+        let big : SliceStackN<32> = SliceStackN::from_slices(&[0; 32], 1).unwrap();
+        assert_eq!(32, big.slices().len());
+
+        assert_eq!(SliceStackError::TooManySlices { count:17, capacity:16 },
+                   SliceStack::from_slices(&[0; 17], 1).err().unwrap());
+        assert_eq!(SliceStackError::ColorOutOfRange { index:2, color:3, type_count:3 },
+                   SliceStack::from_slices(&[0, 1, 3, 2], 3).err().unwrap());
+        assert_eq!(SliceStackError::TypeCountMismatch { type_count:3, colors_present:2 },
+                   SliceStack::from_slices(&[0, 1, 1, 0], 3).err().unwrap());
+        assert_eq!(SliceStackError::TypeCountMismatch { type_count:1, colors_present:0 },
+                   SliceStack::from_slices(&[], 1).err().unwrap());
+    }
+
+    #[test]
+    fn slice_objects_test() {
+        // Zero-sized payloads don't grow the stack
+        assert_eq!(std::mem::size_of::<SliceStack>(),
+                   std::mem::size_of::<SliceStackN<MAX_SLICES, ()>>());
+        assert_eq!(0, std::mem::size_of::<[(); MAX_SLICES]>());
+
+        let plain = SliceStack::from_slices(&[0, 1, 2, 1, 3, 0], 4).unwrap();
+        // Tag each slice with its starting position
+        let start : SliceStack<usize> = plain.with_objects(0..6);
+        assert_eq!(&[0, 1, 2, 3, 4, 5], start.objects());
+
+        let mut a = start;
+        let mut b = start;
+        let mut c = start;
+        for &(index, direction) in [(3, -1), (1, 1), (5, -1), (2, 1), (0, 1)].iter() {
+            a.flip(index, direction);
+            b.flip_unsafe(index, direction);
+            c.flip_rsslice(index, direction);
+            for s in [&a, &b, &c].iter() {
+                assert_eq!(a.slices(), s.slices());
+                assert_eq!(a.objects(), s.objects());
+                // Every payload still sits on the color it started with
+                for (t, o) in s.slices().iter().zip(s.objects()) {
+                    assert_eq!(*t, plain.slices()[*o]);
+                }
+            }
+        }
+
+        *a.object_mut(0).unwrap() = 42;
+        assert_eq!(Some(&42), a.object(0));
+        assert_eq!(None, a.object(6));
+        assert_eq!(a.slices(), a.without_objects().slices());
+    }
+
     #[test]
     fn ss_iscomplete_test() {
         let mut ss : SliceStack = SliceStack {
             count : 4,
             type_count : 3,
-            slice_type : [0; MAX_SLICES]
+            slice_type : [0; MAX_SLICES],
+            slice_object : [(); MAX_SLICES]
         };
         ss.slice_type[0] = 0;
         ss.slice_type[1] = 2;
@@ -525,7 +763,8 @@ mod tests {
         let mut ss : SliceStack = SliceStack {
             count : 4,
             type_count : 3,
-            slice_type : [0; MAX_SLICES]
+            slice_type : [0; MAX_SLICES],
+            slice_object : [(); MAX_SLICES]
         };
         ss.slice_type[0] = 0;
         ss.slice_type[1] = 2;
@@ -543,8 +782,9 @@ mod tests {
         const SS_COUNT : usize = 1;//024*1024;
         let mut ss = vec![SliceStack::new(); SS_COUNT];
 
+        let mut rng = PrngCtxGaloisLsfw::new(34);
         for s in ss.iter_mut() {
-            s.init(SLICE_COUNT, COLOR_COUNT);
+            s.init_with(&mut rng, SLICE_COUNT, COLOR_COUNT);
             let f1 = s.fragmentation();
             let f2 = s.fragmentation2();
             assert!(f1 <= s.count);
@@ -556,46 +796,183 @@ mod tests {
 
     #[test]
     fn ss_find_single_joining_move_test() {
-        // TODO
+        // Left edge color appears again at index 3
+        let ss = SliceStack::from_slices(&[0, 1, 2, 0, 3], 4).unwrap();
+        let expected = Move::new(2, Side::Left);
+        assert_eq!(Some(expected), ss.find_single_joining_move());
+        assert_eq!(Some(expected), ss.find_single_joining_move_unchecked());
+        assert_eq!(Some(expected), ss.find_single_joining_move_rawptr());
+        let mut joined = ss;
+        expected.apply(&mut joined);
+        assert_eq!(&[2, 1, 0, 0, 3], joined.slices());
+        // The right edge (3) doesn't repeat
+        assert_eq!(Some(expected), ss.find_single_joining_move_on(Side::Left));
+        assert_eq!(None, ss.find_single_joining_move_on(Side::Right));
+
+        // Only the right edge color can be joined
+        let ss = SliceStack::from_slices(&[0, 1, 3, 2, 1], 4).unwrap();
+        let expected = Some(Move::new(2, Side::Right));
+        assert_eq!(expected, ss.find_single_joining_move());
+        assert_eq!(expected, ss.find_single_joining_move_unchecked());
+        assert_eq!(expected, ss.find_single_joining_move_rawptr());
+        assert_eq!(expected, ss.find_single_joining_move_on(Side::Right));
+        assert_eq!(None, ss.find_single_joining_move_on(Side::Left));
+
+        // No edge color appears again
+        let ss = SliceStack::from_slices(&[0, 1, 2, 1, 3], 4).unwrap();
+        assert_eq!(None, ss.find_single_joining_move());
+        assert_eq!(None, ss.find_single_joining_move_unchecked());
+        assert_eq!(None, ss.find_single_joining_move_rawptr());
     }
 
-   #[test]
+    #[test]
     fn ss_find_first_double_move_test() {
-        // TODO
+        let ss = SliceStack::from_slices(&[0, 1, 2, 1, 3], 4).unwrap();
+        // From the left, color 1 breaks at index 3; flip it to the right edge
+        let expected = Move::new(3, Side::Right);
+        assert_eq!(Some(expected), ss.find_first_double_move(0));
+        assert_eq!(Some(expected), ss.find_first_double_move_unsafe(0));
+        let mut setup = ss;
+        expected.apply(&mut setup);
+        assert_eq!(&[0, 1, 2, 3, 1], setup.slices());
+        assert!(setup.find_single_joining_move().is_some());
+
+        // From the right, color 1 breaks at index 1
+        let expected = Some(Move::new(1, Side::Left));
+        assert_eq!(expected, ss.find_first_double_move(1));
+        assert_eq!(expected, ss.find_first_double_move_unsafe(1));
+
+        let complete = SliceStack::from_slices(&[0, 0, 2, 1, 1], 3).unwrap();
+        assert_eq!(None, complete.find_first_double_move(0));
+        assert_eq!(None, complete.find_first_double_move_unsafe(1));
     }
 
     #[test]
     fn lots_of_solutions() {
         const SLICE_COUNT : i32 = MAX_SLICES as i32;
         const COLOR_COUNT : i32 = 8;
-        let mut search_dir : i32 = -1;
-        let mut direction : i32;
+        let search_dir : i32 = -1;
 
         const SS_COUNT : usize = 1024*1024;
         let mut ss = vec![SliceStack::new(); SS_COUNT];
 
+        let mut rng = PrngCtxGaloisLsfw::new(34);
         for s in ss.iter_mut() {
-            s.init(SLICE_COUNT, COLOR_COUNT);
+            s.init_with(&mut rng, SLICE_COUNT, COLOR_COUNT);
         }
 
         for s in &mut ss[..] {
             while !s.iscomplete() {
             //loop {
                 //if ss.iscomplete2() { break; }
-                //let mut index = ss.find_single_joining_move(&mut direction).unwrap_or_else(
-                //    || ss.find_first_double_move(search_dir, &mut direction).unwrap_or(
-                //        SLICE_COUNT));
-                direction = 0;
-                let mut index = s.find_single_joining_move_unchecked(&mut direction);
-                if index == -1 { index = s.find_first_double_move(search_dir, &mut direction); }
-                assert!(index != -1);
-                index += direction;
-                s.flip(index, direction);
+                let mv = s.find_single_joining_move_unchecked()
+                    .or_else(|| s.find_first_double_move(search_dir));
+                assert!(mv.is_some());
+                mv.unwrap().apply(s);
                 //search_dir = !search_dir;
             }
         }
     }
 
+    // Solve a stack of any capacity with each flip/finder variant and check
+    // that they all agree with the safe implementation
+    fn solve_capacity<const N : usize>(rng : &mut PrngCtxGaloisLsfw, color_count : i32) {
+        let mut s : SliceStackN<N> = SliceStackN::new();
+        s.init_with(rng, N as i32, color_count);
+        assert_eq!(N, s.capacity());
+        assert_eq!(s.fragmentation(), s.fragmentation2());
+        assert_eq!(s.fragmentation(), s.fragmentation_unsafe());
+
+        let mut a = s;
+        let mut b = s;
+        while !a.iscomplete() {
+            assert_eq!(a.iscomplete(), b.iscomplete_unsafe());
+            let mut mv = a.find_single_joining_move();
+            let mut mv_b = b.find_single_joining_move_unchecked();
+            assert_eq!(mv, mv_b);
+            if mv.is_none() {
+                mv = a.find_first_double_move(0);
+                mv_b = b.find_first_double_move_unsafe(0);
+                assert_eq!(mv, mv_b);
+            }
+            let (index, direction) = (mv.unwrap().index, mv.unwrap().side.direction());
+            a.flip(index, direction);
+            b.flip_unsafe(index, direction);
+            let mut c = b;
+            c.flip_unsafe(index, direction);
+            c.flip_rsslice(index, direction);
+            assert!(a.slice_type[..] == b.slice_type[..]);
+            assert!(b.slice_type[..] == c.slice_type[..]);
+        }
+        assert!(b.iscomplete_unsafe());
+        assert_eq!(a.fragmentation(), a.fragmentation_unsafe());
+    }
+
+    // Reference completion check: every color's slices are contiguous
+    fn naive_iscomplete<const N : usize>(s : &SliceStackN<N>) -> bool {
+        let slices = &s.slice_type[0..s.count as usize];
+        for (i, t) in slices.iter().enumerate() {
+            if let Some(last) = slices.iter().rposition(|u| u == t) {
+                if slices[i..last].iter().any(|u| u != t) {
+                    return false;
+                }
+            }
+        }
+        return true;
+    }
+
+    #[test]
+    fn many_colors_test() {
+        let mut s : SliceStackN<64> = SliceStackN::new();
+        s.count = 4;
+        s.type_count = 256;
+        s.slice_type[0] = 255;
+        s.slice_type[1] = 255;
+        s.slice_type[2] = 40;
+        s.slice_type[3] = 200;
+        assert_eq!(3, s.fragmentation());
+        assert_eq!(3, s.fragmentation_unsafe());
+        assert_eq!(3, s.fragmentation2());
+        assert!(s.iscomplete());
+        assert!(s.iscomplete_unsafe());
+        s.slice_type[3] = 255;
+        assert!(!s.iscomplete());
+        assert!(!s.iscomplete_unsafe());
+        assert_eq!(2, s.colors().len());
+
+        // Full range of colors, solved with both finder variants
+        let mut rng = PrngCtxGaloisLsfw::new(34);
+        for _ in 0..64 {
+            s.init_with(&mut rng, 64, 256);
+            let mut b = s;
+            loop {
+                assert_eq!(naive_iscomplete(&s), s.iscomplete());
+                assert_eq!(s.iscomplete(), b.iscomplete_unsafe());
+                assert_eq!(s.fragmentation(), s.fragmentation2());
+                if s.iscomplete() { break; }
+                let mut mv = s.find_single_joining_move();
+                if mv.is_none() {
+                    mv = s.find_first_double_move(1);
+                    assert_eq!(mv, b.find_first_double_move_unsafe(1));
+                }
+                let mv = mv.unwrap();
+                mv.apply(&mut s);
+                b.flip_unsafe(mv.index, mv.side.direction());
+            }
+            assert_eq!(s.colors().len() as i32, s.fragmentation());
+        }
+    }
+
+    #[test]
+    fn large_capacity_test() {
+        let mut rng = PrngCtxGaloisLsfw::new(34);
+        for _ in 0..64 {
+            solve_capacity::<24>(&mut rng, 8);
+            solve_capacity::<32>(&mut rng, 12);
+            solve_capacity::<64>(&mut rng, 48);
+        }
+    }
+
 }
 
 /* vim: set ts=4 sts=4 sw=4 et : */
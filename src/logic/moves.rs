@@ -0,0 +1,196 @@
+/*
+    Copyright (C) 2016  Erik Beran
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use super::SliceStackN;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+// Edge of the stack a flip is anchored to
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl Side {
+    // Direction value used by SliceStackN::flip and friends
+    pub fn direction(self) -> i32 {
+        match self {
+            Side::Left => -1,
+            Side::Right => 1,
+        }
+    }
+
+    // flip treats any positive direction as the right edge
+    pub fn from_direction(direction : i32) -> Side {
+        if direction > 0 { Side::Right } else { Side::Left }
+    }
+
+    pub fn opposite(self) -> Side {
+        match self {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+        }
+    }
+}
+
+// A single flip of the stack.
+// Left reverses slices 0..=index, Right reverses slices index..count.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Move {
+    pub index : i32,
+    pub side : Side,
+}
+
+impl Move {
+    pub fn new(index : i32, side : Side) -> Move { Move { index, side } }
+
+    // Flip everything on `side` of slice `found`, leaving `found` itself in
+    // place. This is how the move finders turn the slice they located into
+    // the flip to make (the old "index += direction" step).
+    pub fn beyond(found : i32, side : Side) -> Move {
+        return Move { index:found + side.direction(), side };
+    }
+
+    // Whether the flip index is inside a stack of `count` slices. Flips of a
+    // single slice (Left 0, Right count-1) are legal but change nothing.
+    pub fn is_legal(&self, count : i32) -> bool {
+        return self.index >= 0 && self.index < count;
+    }
+
+    // Every flip that can change a stack of `count` slices: Left 1..count
+    // and Right 1..count-1. Flipping a single slice does nothing, and Right 0
+    // reverses the same run as Left count-1, so each flip appears once.
+    pub fn all(count : i32) -> impl Iterator<Item = Move> {
+        let lefts = (1..count).map(|i| Move::new(i, Side::Left));
+        let rights = (1..(count - 1)).map(|i| Move::new(i, Side::Right));
+        return lefts.chain(rights);
+    }
+
+    pub fn apply<const N : usize, T>(&self, s : &mut SliceStackN<N, T>) {
+        s.flip(self.index, self.side.direction());
+    }
+
+    // A flip reverses a contiguous run, so doing it twice restores the stack;
+    // every move is its own inverse.
+    pub fn inverse(&self) -> Move {
+        return *self;
+    }
+}
+
+// Move notation: the side's initial and the index, "L3" or "R5"
+impl fmt::Display for Move {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        let side = match self.side { Side::Left => 'L', Side::Right => 'R' };
+        return write!(f, "{}{}", side, self.index);
+    }
+}
+
+// The text that isn't a move
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseMoveError(pub String);
+
+impl fmt::Display for ParseMoveError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "expected a move like L3 or R5, found {:?}", self.0)
+    }
+}
+
+impl Error for ParseMoveError {}
+
+impl FromStr for Move {
+    type Err = ParseMoveError;
+
+    fn from_str(s : &str) -> Result<Move, ParseMoveError> {
+        let text = s.trim();
+        let side = match text.chars().next() {
+            Some('L') | Some('l') => Side::Left,
+            Some('R') | Some('r') => Side::Right,
+            _ => return Err(ParseMoveError(s.to_string())),
+        };
+        match text[1..].parse::<i32>() {
+            Ok(index) if index >= 0 => return Ok(Move::new(index, side)),
+            _ => return Err(ParseMoveError(s.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use logic::SliceStack;
+
+    #[test]
+    fn move_beyond_test() {
+        assert_eq!(Move::new(2, Side::Left), Move::beyond(3, Side::Left));
+        assert_eq!(Move::new(4, Side::Right), Move::beyond(3, Side::Right));
+        assert_eq!(Side::Right, Side::from_direction(1));
+        assert_eq!(Side::Left, Side::from_direction(-1));
+        assert_eq!(Side::Left, Side::Right.opposite());
+        assert!(Move::new(0, Side::Left).is_legal(1));
+        assert!(!Move::new(1, Side::Right).is_legal(1));
+        assert!(!Move::new(-1, Side::Left).is_legal(4));
+    }
+
+    #[test]
+    fn move_all_test() {
+        assert_eq!(0, Move::all(0).count());
+        assert_eq!(0, Move::all(1).count());
+        assert_eq!(vec![Move::new(1, Side::Left)], Move::all(2).collect::<Vec<_>>());
+        let moves : Vec<Move> = Move::all(5).collect();
+        assert_eq!(7, moves.len());
+        assert!(moves.iter().all(|mv| mv.is_legal(5)));
+        assert!(moves.contains(&Move::new(3, Side::Right)));
+        assert!(!moves.contains(&Move::new(0, Side::Right)));
+        assert!(!moves.contains(&Move::new(4, Side::Right)));
+    }
+
+    #[test]
+    fn move_apply_inverse_test() {
+        let start = SliceStack::from_slices(&[0, 1, 2, 3, 4], 5).unwrap();
+        let mut s = start;
+        let left = Move::new(2, Side::Left);
+        left.apply(&mut s);
+        assert_eq!(&[2, 1, 0, 3, 4], s.slices());
+        left.inverse().apply(&mut s);
+        assert_eq!(start.slices(), s.slices());
+
+        let right = Move::new(1, Side::Right);
+        right.apply(&mut s);
+        assert_eq!(&[0, 4, 3, 2, 1], s.slices());
+        right.inverse().apply(&mut s);
+        assert_eq!(start.slices(), s.slices());
+    }
+
+    #[test]
+    fn move_notation_test() {
+        assert_eq!("L3", Move::new(3, Side::Left).to_string());
+        assert_eq!("R12", Move::new(12, Side::Right).to_string());
+        assert_eq!(Ok(Move::new(3, Side::Left)), "L3".parse());
+        assert_eq!(Ok(Move::new(5, Side::Right)), " r5 ".parse());
+        for mv in Move::all(16) {
+            assert_eq!(Ok(mv), mv.to_string().parse());
+        }
+        assert_eq!(Err(ParseMoveError("X3".to_string())), "X3".parse::<Move>());
+        assert!("L".parse::<Move>().is_err());
+        assert!("L-1".parse::<Move>().is_err());
+        assert!("".parse::<Move>().is_err());
+    }
+}
+
+/* vim: set ts=4 sts=4 sw=4 et : */
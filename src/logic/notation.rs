@@ -0,0 +1,270 @@
+/*
+    Copyright (C) 2016  Erik Beran
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+// Text notation for stacks, left edge first.
+//
+// Up to 26 colors are written one letter per slice, color 0 as 'A':
+//     "ABBCA"
+// Stacks with more colors use comma separated color ids:
+//     "0,27,27,3"
+// type_count is one more than the highest color unless a "/count" suffix
+// says otherwise, which Display adds only when needed:
+//     "ACCA/8"
+// Colors may be skipped, as init_with deals them, so every stack
+// round-trips.
+
+use super::{SliceStackError, SliceStackN, SliceType};
+use std::error::Error;
+use std::fmt;
+use std::mem;
+use std::str::FromStr;
+
+const LETTER_COLORS : i32 = 26;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseStackError {
+    // Letter form: `found` at char `position` isn't 'A'..='Z'
+    InvalidLetter { position : usize, found : char },
+    // Numeric form: entry number `position` isn't a color id
+    InvalidNumber { position : usize, text : String },
+    // The "/count" suffix isn't a number
+    InvalidTypeCount { text : String },
+    // Well formed, but not a valid stack
+    Invalid(SliceStackError),
+}
+
+impl fmt::Display for ParseStackError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseStackError::InvalidLetter { position, found } =>
+                write!(f, "expected a color letter A-Z at position {}, found {:?}",
+                       position, found),
+            ParseStackError::InvalidNumber { position, ref text } =>
+                write!(f, "expected a color id 0-{} for slice {}, found {:?}",
+                       SliceType::MAX, position, text),
+            ParseStackError::InvalidTypeCount { ref text } =>
+                write!(f, "expected a color count after '/', found {:?}", text),
+            ParseStackError::Invalid(ref e) => write!(f, "invalid stack: {}", e),
+        }
+    }
+}
+
+impl Error for ParseStackError {}
+
+impl From<SliceStackError> for ParseStackError {
+    fn from(e : SliceStackError) -> ParseStackError { ParseStackError::Invalid(e) }
+}
+
+impl<const N : usize, T> SliceStackN<N, T> {
+    fn uses_letters(&self) -> bool {
+        return self.type_count <= LETTER_COLORS
+            && self.slices().iter().all(|t| (*t as i32) < LETTER_COLORS);
+    }
+
+    // type_count the notation implies without a suffix
+    fn implied_type_count(&self) -> i32 {
+        return self.slices().iter().map(|t| *t as i32 + 1).max().unwrap_or(0);
+    }
+}
+
+impl<const N : usize, T> fmt::Display for SliceStackN<N, T> {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        if self.uses_letters() {
+            for t in self.slices().iter() {
+                write!(f, "{}", (b'A' + *t) as char)?;
+            }
+        } else {
+            for (i, t) in self.slices().iter().enumerate() {
+                if i > 0 {
+                    write!(f, ",")?;
+                }
+                write!(f, "{}", t)?;
+            }
+        }
+        if self.type_count != self.implied_type_count() {
+            write!(f, "/{}", self.type_count)?;
+        }
+        return Ok(());
+    }
+}
+
+// Shows the notation instead of the raw arrays; payloads only when they
+// carry data.
+impl<const N : usize, T : fmt::Debug> fmt::Debug for SliceStackN<N, T> {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        let mut t = f.debug_tuple("SliceStack");
+        t.field(&format_args!("{}", self));
+        if mem::size_of::<T>() != 0 {
+            t.field(&self.objects());
+        }
+        return t.finish();
+    }
+}
+
+impl<const N : usize> FromStr for SliceStackN<N> {
+    type Err = ParseStackError;
+
+    fn from_str(s : &str) -> Result<SliceStackN<N>, ParseStackError> {
+        let (s, suffix) = match s.trim().split_once('/') {
+            Some((s, suffix)) => (s.trim(), Some(suffix.trim())),
+            None => (s.trim(), None),
+        };
+        let mut slices : Vec<SliceType> = Vec::new();
+        if s.chars().any(|c| c.is_ascii_digit()) {
+            for (i, text) in s.split(',').enumerate() {
+                match text.trim().parse::<SliceType>() {
+                    Ok(t) => slices.push(t),
+                    Err(_) => return Err(ParseStackError::InvalidNumber { position:i,
+                        text:text.to_string() }),
+                }
+            }
+        } else {
+            for (i, c) in s.chars().enumerate() {
+                if !c.is_ascii_uppercase() {
+                    return Err(ParseStackError::InvalidLetter { position:i, found:c });
+                }
+                slices.push(c as SliceType - b'A');
+            }
+        }
+        let type_count = match suffix {
+            Some(text) => match text.parse::<i32>() {
+                Ok(n) => n,
+                Err(_) => return Err(ParseStackError::InvalidTypeCount { text:text.to_string() }),
+            },
+            None => slices.iter().map(|t| *t as i32 + 1).max().unwrap_or(0),
+        };
+        return Ok(SliceStackN::from_slices_with_gaps(&slices, type_count)?);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use logic::*;
+    use prng::*;
+
+    #[test]
+    fn notation_letters_test() {
+        let s : SliceStack = "ABBCA".parse().unwrap();
+        assert_eq!(&[0, 1, 1, 2, 0], s.slices());
+        assert_eq!(3, s.type_count());
+        assert_eq!("ABBCA", s.to_string());
+        assert_eq!("SliceStack(ABBCA)", format!("{:?}", s));
+        assert_eq!(s, " ABBCA\n".parse().unwrap());
+
+        let empty : SliceStack = "".parse().unwrap();
+        assert_eq!(0, empty.count());
+        assert_eq!("", empty.to_string());
+
+        let objects : SliceStack<u32> = s.with_objects(vec![7, 8]);
+        assert_eq!("SliceStack(ABBCA, [7, 8, 0, 0, 0])", format!("{:?}", objects));
+    }
+
+    #[test]
+    fn notation_numbers_test() {
+        let ids : Vec<SliceType> = (0..30).rev().collect();
+        let s : SliceStackN<32> = SliceStackN::from_slices(&ids, 30).unwrap();
+        let text = s.to_string();
+        assert!(text.starts_with("29,28,27,"));
+        assert!(text.ends_with(",1,0"));
+        assert_eq!(s, text.parse().unwrap());
+        // Spaces around entries are fine
+        let s : SliceStack = "1, 0 ,1".parse().unwrap();
+        assert_eq!(&[1, 0, 1], s.slices());
+    }
+
+    #[test]
+    fn notation_errors_test() {
+        assert_eq!(Err(ParseStackError::InvalidLetter { position:2, found:'b' }),
+                   "ABbA".parse::<SliceStack>());
+        assert_eq!(Err(ParseStackError::InvalidLetter { position:1, found:' ' }),
+                   "A B".parse::<SliceStack>());
+        assert_eq!(Err(ParseStackError::InvalidNumber { position:1, text:"".to_string() }),
+                   "0,,1".parse::<SliceStack>());
+        assert_eq!(Err(ParseStackError::InvalidNumber { position:2, text:"256".to_string() }),
+                   "0,1,256".parse::<SliceStack>());
+        // Colors must be below the given type_count
+        assert_eq!(Err(ParseStackError::Invalid(SliceStackError::ColorOutOfRange {
+                       index:1, color:2, type_count:2 })),
+                   "AC/2".parse::<SliceStack>());
+        assert_eq!(Err(ParseStackError::InvalidTypeCount { text:"x".to_string() }),
+                   "AC/x".parse::<SliceStack>());
+        assert_eq!(Err(ParseStackError::Invalid(SliceStackError::TooManySlices {
+                       count:17, capacity:16 })),
+                   "ABABABABABABABABA".parse::<SliceStack>());
+        let e = "ABbA".parse::<SliceStack>().unwrap_err();
+        assert_eq!("expected a color letter A-Z at position 2, found 'b'", e.to_string());
+    }
+
+    #[test]
+    fn notation_type_count_test() {
+        // Skipped colors are fine; the highest one sets type_count
+        let s : SliceStack = "AC".parse().unwrap();
+        assert_eq!(3, s.type_count());
+        assert_eq!("AC", s.to_string());
+        // Colors above the highest present need the suffix
+        let s : SliceStack = "ACCA/8".parse().unwrap();
+        assert_eq!(&[0, 2, 2, 0], s.slices());
+        assert_eq!(8, s.type_count());
+        assert_eq!("ACCA/8", s.to_string());
+        let s : SliceStackN<32> = " 27, 0 / 30".parse().unwrap();
+        assert_eq!(30, s.type_count());
+        assert_eq!("27,0/30", s.to_string());
+    }
+
+    #[test]
+    fn notation_random_round_trip_test() {
+        let mut rng = PrngCtxSplitMix64::new(8);
+        for _ in 0..1000 {
+            let mut s = SliceStack::new();
+            s.init_with(&mut rng, MAX_SLICES as i32, 8);
+            let back : SliceStack = s.to_string().parse().unwrap();
+            assert_eq!(s, back);
+            assert_eq!(s.type_count(), back.type_count());
+        }
+        let mut rng = PrngCtxSplitMix64::new(9);
+        for _ in 0..100 {
+            let mut s = SliceStackN::<40>::new();
+            s.init_with(&mut rng, 40, 30);
+            let back : SliceStackN<40> = s.to_string().parse().unwrap();
+            assert_eq!(s, back);
+            assert_eq!(s.type_count(), back.type_count());
+        }
+    }
+
+    #[test]
+    fn notation_round_trip_test() {
+        // Every valid stack of up to 5 slices and 3 colors
+        for count in 0..6usize {
+            let total = 3usize.pow(count as u32);
+            for mut n in 0..total {
+                let mut slices = Vec::new();
+                for _ in 0..count {
+                    slices.push((n % 3) as SliceType);
+                    n /= 3;
+                }
+                let distinct = (0..3).filter(|c| slices.contains(c)).count() as i32;
+                if let Ok(s) = SliceStack::from_slices(&slices, distinct) {
+                    let back : SliceStack = s.to_string().parse().unwrap();
+                    assert_eq!(s, back);
+                    assert_eq!(s.type_count(), back.type_count());
+                }
+            }
+        }
+    }
+}
+
+/* vim: set ts=4 sts=4 sw=4 et : */
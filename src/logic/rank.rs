@@ -0,0 +1,263 @@
+/*
+    Copyright (C) 2016  Erik Beran
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+// Perfect ranking: stack layouts numbered 0..states with no gaps, so tables
+// over every stack of a size can be flat arrays.
+//
+// rank() reads the slices as a base type_count number, slice 0 the most
+// significant digit, giving 0..type_count^count. Layouts that leave some
+// colors out are included; from_slices would reject those, so unrank builds
+// the stack directly.
+//
+// rank_relabeled() numbers relabel classes instead (see relabeled()): the
+// relabeled slices are restricted growth strings, where each slice is at
+// most one above the largest color before it.
+
+use super::{SliceStackError, SliceStackN, SliceType};
+
+const COLOR_RANGE : usize = SliceType::MAX as usize + 1;
+
+// Number of layouts of `count` slices using colors 0..type_count; None if
+// that doesn't fit a u64
+pub fn state_count(count : i32, type_count : i32) -> Option<u64> {
+    return (type_count as u64).checked_pow(count as u32);
+}
+
+// Number of relabel classes of `count` slices using at most type_count
+// colors; None if that doesn't fit a u64
+pub fn relabeled_state_count(count : i32, type_count : i32) -> Option<u64> {
+    let table = completions(count, type_count)?;
+    return Some(table[0][0]);
+}
+
+// completions[i][m]: restricted growth strings for slices i.. when the
+// slices before i use colors 0..m
+fn completions(count : i32, type_count : i32) -> Option<Vec<Vec<u64>>> {
+    let n = count as usize;
+    let k = type_count as usize;
+    let mut table = vec![vec![0u64; k + 1]; n + 1];
+    // Only the empty string is left
+    table[n] = vec![1; k + 1];
+    for i in (0..n).rev() {
+        for m in 0..=k {
+            let mut total = (m as u64).checked_mul(table[i + 1][m])?;
+            if m < k {
+                total = total.checked_add(table[i + 1][m + 1])?;
+            }
+            table[i][m] = total;
+        }
+    }
+    return Some(table);
+}
+
+impl<const N : usize, T> SliceStackN<N, T> {
+    // Position of the layout in 0..state_count(count, type_count); None
+    // where state_count is
+    pub fn rank(&self) -> Option<u64> {
+        // Every rank is below the state count, so it fits once that does
+        state_count(self.count, self.type_count)?;
+        let base = self.type_count as u64;
+        let mut r : u64 = 0;
+        for t in self.slices().iter() {
+            r = r * base + *t as u64;
+        }
+        return Some(r);
+    }
+
+    // Position of the relabeled layout in 0..relabeled_state_count(count,
+    // type_count), None where that is. Stacks that differ only by color
+    // labels share a rank.
+    pub fn rank_relabeled(&self) -> Option<u64> {
+        return self.rank_relabeled_in(self.type_count);
+    }
+
+    // rank_relabeled() among the stacks with up to `type_count` colors, which
+    // may be more than this stack's; None if it uses more colors than that
+    pub fn rank_relabeled_in(&self, type_count : i32) -> Option<u64> {
+        if self.colors().len() as i32 > type_count {
+            return None;
+        }
+        let table = completions(self.count, type_count)?;
+        let mut label : [Option<SliceType>; COLOR_RANGE] = [None; COLOR_RANGE];
+        // Labels handed out before the current slice
+        let mut m : usize = 0;
+        let mut r : u64 = 0;
+        for (i, t) in self.slices().iter().enumerate() {
+            let v = match label[*t as usize] {
+                Some(l) => l as usize,
+                None => m,
+            };
+            // Every smaller label is an old one and leaves m labels in use
+            r += v as u64 * table[i + 1][m];
+            if v == m {
+                label[*t as usize] = Some(m as SliceType);
+                m += 1;
+            }
+        }
+        return Some(r);
+    }
+}
+
+impl<const N : usize> SliceStackN<N> {
+    // Inverse of rank()
+    pub fn unrank(rank : u64, count : i32, type_count : i32)
+        -> Result<SliceStackN<N>, SliceStackError> {
+        if count as usize > N {
+            return Err(SliceStackError::TooManySlices { count:count as usize, capacity:N });
+        }
+        // Past u64 every rank is in range
+        if let Some(states) = state_count(count, type_count) {
+            if rank >= states {
+                return Err(SliceStackError::RankOutOfRange { rank, states });
+            }
+        }
+        let mut s = SliceStackN::new();
+        s.count = count;
+        s.type_count = type_count;
+        let mut r = rank;
+        for i in (0..count as usize).rev() {
+            s.slice_type[i] = (r % type_count as u64) as SliceType;
+            r /= type_count as u64;
+        }
+        return Ok(s);
+    }
+
+    // Inverse of rank_relabeled(); the stack comes back relabeled
+    pub fn unrank_relabeled(rank : u64, count : i32, type_count : i32)
+        -> Result<SliceStackN<N>, SliceStackError> {
+        if count as usize > N {
+            return Err(SliceStackError::TooManySlices { count:count as usize, capacity:N });
+        }
+        let table = match completions(count, type_count) {
+            Some(table) => table,
+            None => return Err(SliceStackError::TooManyStates { count, type_count }),
+        };
+        let states = table[0][0];
+        if rank >= states {
+            return Err(SliceStackError::RankOutOfRange { rank, states });
+        }
+        let mut s = SliceStackN::new();
+        s.count = count;
+        s.type_count = type_count;
+        let mut m : usize = 0;
+        let mut r = rank;
+        for i in 0..count as usize {
+            let block = table[i + 1][m];
+            let v = if r < m as u64 * block {
+                (r / block) as usize
+            } else {
+                m
+            };
+            r -= v as u64 * block;
+            s.slice_type[i] = v as SliceType;
+            if v == m {
+                m += 1;
+            }
+        }
+        return Ok(s);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use logic::*;
+    use prng::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn rank_round_trip_test() {
+        for count in 0..7 {
+            for type_count in 1..5 {
+                let states = state_count(count, type_count).unwrap();
+                for r in 0..states {
+                    let s = SliceStack::unrank(r, count, type_count).unwrap();
+                    assert_eq!(count, s.count());
+                    assert_eq!(Some(r), s.rank());
+                }
+                assert_eq!(SliceStackError::RankOutOfRange { rank:states, states },
+                           SliceStack::unrank(states, count, type_count).unwrap_err());
+            }
+        }
+        let s : SliceStack = "CABA".parse().unwrap();
+        // 2,0,1,0 in base 3
+        assert_eq!(Some(57), s.rank());
+        assert_eq!(None, state_count(16, 256));
+        assert!(SliceStackN::<4>::unrank(0, 5, 2).is_err());
+    }
+
+    #[test]
+    fn rank_relabeled_round_trip_test() {
+        for count in 0..8 {
+            for type_count in 1..5 {
+                let states = relabeled_state_count(count, type_count).unwrap();
+                let mut classes = HashSet::new();
+                for r in 0..state_count(count, type_count).unwrap() {
+                    let s = SliceStack::unrank(r, count, type_count).unwrap();
+                    let class = s.rank_relabeled().unwrap();
+                    assert!(class < states);
+                    let back = SliceStack::unrank_relabeled(class, count, type_count).unwrap();
+                    assert_eq!(s.relabeled(), back);
+                    classes.insert(class);
+                }
+                // Every rank is used
+                assert_eq!(states, classes.len() as u64);
+            }
+        }
+        // Bell number: every partition of 5 slices
+        assert_eq!(Some(52), relabeled_state_count(5, 5));
+        assert_eq!(SliceStackError::RankOutOfRange { rank:52, states:52 },
+                   SliceStack::unrank_relabeled(52, 5, 5).unwrap_err());
+    }
+
+    #[test]
+    fn rank_relabeled_in_test() {
+        let s : SliceStack = "ABCAB".parse().unwrap();
+        assert_eq!(s.rank_relabeled(), s.rank_relabeled_in(3));
+        let r = s.rank_relabeled_in(5).unwrap();
+        let back = SliceStack::unrank_relabeled(r, 5, 5).unwrap();
+        assert_eq!(s.slices(), back.slices());
+        assert_eq!(None, s.rank_relabeled_in(2));
+    }
+
+    #[test]
+    fn rank_overflow_test() {
+        let mut rng = PrngCtxSplitMix64::new(16);
+        let mut s = SliceStackN::<32>::new();
+        s.init_with(&mut rng, 32, 5);
+        assert_eq!(None, state_count(32, 5));
+        assert_eq!(None, s.rank());
+        assert_eq!(None, s.rank_relabeled());
+        assert_eq!(SliceStackError::TooManyStates { count:32, type_count:5 },
+                   SliceStackN::<32>::unrank_relabeled(0, 32, 5).unwrap_err());
+
+        let mut s = SliceStack::new();
+        s.init_with(&mut rng, 16, 256);
+        assert_eq!(None, s.rank());
+        assert_eq!(None, s.rank_relabeled());
+        // 16^16 = 2^64 layouts don't fit, even though the last rank would
+        assert_eq!(None, state_count(16, 16));
+        let last : SliceStack = SliceStack::unrank(u64::MAX, 16, 16).unwrap();
+        assert_eq!(None, last.rank());
+        assert_eq!(None, SliceStack::unrank(0, 16, 16).unwrap().rank());
+        // 15^16 do
+        let states = state_count(16, 15).unwrap();
+        let last : SliceStack = SliceStack::unrank(states - 1, 16, 15).unwrap();
+        assert_eq!(Some(states - 1), last.rank());
+    }
+}
+
+/* vim: set ts=4 sts=4 sw=4 et : */
@@ -0,0 +1,36 @@
+/*
+    Copyright (C) 2016  Erik Beran
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+// Helpers for the unit tests across the crate
+
+use super::SliceStack;
+use prng::Prng;
+
+// The stack written in notation, e.g. "ABCBD"
+pub fn stack(text : &str) -> SliceStack { text.parse().unwrap() }
+
+// `n` stacks dealt from `rng` one after another with init_with
+pub fn random_stacks<R : Prng>(rng : &mut R, n : usize, count : i32, type_count : i32)
+    -> Vec<SliceStack> {
+    let mut stacks = vec![SliceStack::new(); n];
+    for s in stacks.iter_mut() {
+        s.init_with(rng, count, type_count);
+    }
+    return stacks;
+}
+
+/* vim: set ts=4 sts=4 sw=4 et : */
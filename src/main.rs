@@ -2,107 +2,124 @@
 // Disable warning on unused function/method; we have duplicates with different
 // implementations for performance investigations
 #![allow(dead_code)]
+// Explicit `return` statements are used throughout the crate
+#![allow(clippy::needless_return)]
 
 extern crate rayon;
+extern crate sortgame_rs;
 
-mod logic;
-mod prng;
-
-use logic::*;
 use rayon::prelude::*;
+use sortgame_rs::logic::*;
+use sortgame_rs::prng::*;
+use sortgame_rs::solver::*;
 use std::env;
 
-#[derive(Debug, PartialEq)]
-enum RunSafety {
-    AllSafe,
-    SomeSafe,
-    NoSafe,
+// Solutions are applied, leaving every stack complete
+fn solve_all_stacks_par<S : Solver + Sync + ?Sized>(ss : &mut Vec<SliceStack>, solver : &S) {
+    ss.par_iter_mut().for_each(|s| *s = solver.solve(s).apply(s));
 }
 
-fn solve_all_stacks_par(ss : &mut Vec<SliceStack>, run_safety : RunSafety) {
-    match run_safety {
-        RunSafety::AllSafe => ss.par_iter_mut() .for_each(|s| solve_stack_all_safe(s)),
-        RunSafety::SomeSafe => ss.par_iter_mut() .for_each(|s| solve_stack_some_safe(s)),
-        RunSafety::NoSafe => ss.par_iter_mut() .for_each(|s| solve_stack_no_safe(s))
-    }
+// Same signature as solve_all_stacks_par
+#[allow(clippy::ptr_arg)]
+fn solve_all_stacks<S : Solver + ?Sized>(ss : &mut Vec<SliceStack>, solver : &S) {
+    for s in &mut ss[..] { *s = solver.solve(s).apply(s) }
 }
 
-fn solve_all_stacks(ss : &mut Vec<SliceStack>, run_safety : RunSafety) {
-    match run_safety {
-        RunSafety::AllSafe => for s in &mut ss[..] { solve_stack_all_safe(s) },
-        RunSafety::SomeSafe => for s in &mut ss[..] { solve_stack_some_safe(s) },
-        RunSafety::NoSafe => for s in &mut ss[..] { solve_stack_no_safe(s) }
-    }
-}
+const SLICE_COUNT : i32 = MAX_SLICES as i32;
+const COLOR_COUNT : i32 = 8;
+const SS_COUNT : usize = 1024*1024;
 
-fn solve_stack_all_safe(s : &mut SliceStack) {
-    let mut direction : i32;
-    let search_dir : i32 = 0;
-    while !s.iscomplete() {
-        direction = 0;
-        let mut index = s.find_single_joining_move(&mut direction);
-        if index == -1 { index = s.find_first_double_move(search_dir, &mut direction); }
-        //assert!(index != -1);
-        index += direction;
-        s.flip(index, direction);
-        //search_dir = !search_dir;
-    }
-}
+// Seed the old global generator started from
+const SEED : u32 = 34;
 
-fn solve_stack_some_safe(s : &mut SliceStack) {
-    let mut direction : i32;
-    let search_dir : i32 = 0;
-    while !s.iscomplete_unsafe() {
-        direction = 0;
-        let mut index = s.find_single_joining_move_unchecked(&mut direction);
-        if index == -1 { index = s.find_first_double_move(search_dir, &mut direction); }
-        //assert!(index != -1);
-        index += direction;
-        s.flip_rsslice(index, direction);
-        //search_dir = !search_dir;
+fn big_run(solver : &dyn Solver) {
+    let mut rng = PrngCtxGaloisLsfw::new(SEED);
+    let mut ss = vec![SliceStack::new(); SS_COUNT];
+    for s in ss.iter_mut() {
+        s.init_with_unsafe(&mut rng, SLICE_COUNT, COLOR_COUNT);
     }
+    solve_all_stacks(&mut ss, solver);
 }
 
-fn solve_stack_no_safe(s : &mut SliceStack) {
-    let mut direction : i32;
-    let search_dir : i32 = 0;
-    while !s.iscomplete_unsafe() {
-        direction = 0;
-        let mut index = s.find_single_joining_move_rawptr(&mut direction);
-        if index == -1 { index = s.find_first_double_move_unsafe(search_dir, &mut direction); }
-        //assert!(index != -1);
-        index += direction;
-        //s.flip_rsslice(index, direction);
-        s.flip_unsafe(index, direction);
-        //search_dir = !search_dir;
+const COMPARE_COUNT : usize = 1000;
+
+// Average moves per greedy policy over the same generated stacks
+fn compare_policies() {
+    let mut rng = PrngCtxGaloisLsfw::new(SEED);
+    let mut corpus = vec![SliceStack::new(); COMPARE_COUNT];
+    for s in corpus.iter_mut() {
+        s.init_with(&mut rng, SLICE_COUNT, COLOR_COUNT);
+    }
+    let policies : Vec<(&str, Box<dyn Solver>)> = vec![
+        ("left-first", Box::new(PolicyGreedy::new(LeftFirst))),
+        ("right-first", Box::new(PolicyGreedy::new(RightFirst))),
+        ("alternating", Box::new(PolicyGreedy::new(Alternating::new()))),
+        ("random", Box::new(PolicyGreedy::new(SeededRandom::new(SEED as u64)))),
+        ("lookahead-1", Box::new(PolicyGreedy::new(Lookahead::new(1)))),
+        ("lookahead-2", Box::new(PolicyGreedy::new(Lookahead::new(2)))),
+    ];
+    for (name, solver) in policies.iter() {
+        println!("{:<12} {:.3}", name, average_moves(&**solver, &corpus));
     }
 }
 
-const SLICE_COUNT : i32 = MAX_SLICES as i32;
-const COLOR_COUNT : i32 = 8;
-const SS_COUNT : usize = 1024*1024;
+const MCTS_COMPARE_COUNT : usize = 100;
 
-fn big_run(run_safety : RunSafety) {
-    let mut ss = vec![SliceStack::new(); SS_COUNT];
-    for s in ss.iter_mut() {
-        s.init_unsafe(SLICE_COUNT, COLOR_COUNT);
+// MctsSolver against the greedy solver on the same generated stacks of
+// `count` slices
+fn compare_mcts_on<const N : usize>(count : i32, type_count : i32) {
+    let mut rng = PrngCtxGaloisLsfw::new(SEED);
+    let greedy = GreedySolver::default();
+    let mcts = MctsSolver::new(SEED as u64);
+    let (mut greedy_total, mut mcts_total) = (0, 0);
+    let (mut shorter, mut optimal) = (0, 0);
+    for _ in 0..MCTS_COMPARE_COUNT {
+        let mut s = SliceStackN::<N>::new();
+        s.init_with(&mut rng, count, type_count);
+        let greedy_len = greedy.solve(&s).len();
+        let solution = mcts.solve(&s);
+        greedy_total += greedy_len;
+        mcts_total += solution.len();
+        if solution.len() < greedy_len {
+            shorter += 1;
+        }
+        if solution.is_optimal() {
+            optimal += 1;
+        }
     }
-    solve_all_stacks(&mut ss, run_safety);
+    let n = MCTS_COMPARE_COUNT as f64;
+    println!("{} slices, {} colors: greedy {:.3}, mcts {:.3}; mcts shorter on {} of {}, \
+              proven optimal on {}", count, type_count, greedy_total as f64 / n,
+             mcts_total as f64 / n, shorter, MCTS_COMPARE_COUNT, optimal);
+}
+
+fn compare_mcts() {
+    compare_mcts_on::<MAX_SLICES>(SLICE_COUNT, COLOR_COUNT);
+    compare_mcts_on::<32>(32, 12);
+    compare_mcts_on::<64>(64, 16);
 }
 
 fn main() {
-    let run_safety : RunSafety =
+    match env::args().nth(1).as_deref() {
+        Some("compare") => return compare_policies(),
+        Some("mcts") => return compare_mcts(),
+        _ => {}
+    }
+
+    let solver : Box<dyn Solver> =
         match env::args().nth(1) {
             Some(arg1) =>
                 match arg1.parse() {
-                    Ok(0) => RunSafety::NoSafe,
-                    Ok(1) => RunSafety::SomeSafe,
-                    Ok(2) => RunSafety::AllSafe,
-                    _ => RunSafety::NoSafe },
-            _ => RunSafety::NoSafe
+                    Ok(0) => Box::new(GreedySolver::new(RunSafety::NoSafe)),
+                    Ok(1) => Box::new(GreedySolver::new(RunSafety::SomeSafe)),
+                    Ok(2) => Box::new(GreedySolver::new(RunSafety::AllSafe)),
+                    Ok(3) => Box::new(IdaSolver::new()),
+                    Ok(4) => Box::new(BeamSolver::default()),
+                    _ => Box::new(GreedySolver::new(RunSafety::NoSafe)) },
+            _ => Box::new(GreedySolver::new(RunSafety::NoSafe))
         };
 
-    big_run(run_safety);
+    big_run(&*solver);
 }
 
 /* vim: set ts=4 sts=4 sw=4 et : */
@@ -1,8 +1,73 @@
+// Source of random numbers for level generation. Each generator owns its
+// state, so generation is reproducible per seed and needs no globals.
+pub trait Prng {
+    fn next_u32(&mut self) -> u32;
 
+    // Two 32 bit draws, high half first. 64 bit generators override this and
+    // derive next_u32 from it instead.
+    fn next_u64(&mut self) -> u64 {
+        let hi = self.next_u32() as u64;
+        let lo = self.next_u32() as u64;
+        return (hi << 32) | lo;
+    }
+
+    // Restart the sequence; the same seed always gives the same sequence
+    fn seed(&mut self, seed : u64);
+
+    // Integer in min..=max, mapped the same way as
+    // prng_galois_lsfw_int_minmax. The end points are half as likely as the
+    // values between them; kept so existing levels generate unchanged.
+    fn int_minmax(&mut self, min : i32, max : i32) -> i32 {
+        return u32_to_minmax(self.next_u32(), min, max);
+    }
+
+    // Uniform integer in 0..bound (bound > 0), rejecting the draws that
+    // would bias the result toward small values
+    fn next_bounded(&mut self, bound : u32) -> u32 {
+        assert!(bound > 0);
+        let threshold = bound.wrapping_neg() % bound;
+        loop {
+            let r = self.next_u32();
+            if r >= threshold {
+                return r % bound;
+            }
+        }
+    }
+
+    // Uniform integer in min..=max
+    fn int_range(&mut self, min : i32, max : i32) -> i32 {
+        assert!(min <= max);
+        let span = (max as i64 - min as i64 + 1) as u64;
+        if span > u32::MAX as u64 {
+            return self.next_u32() as i32;
+        }
+        return (min as i64 + self.next_bounded(span as u32) as i64) as i32;
+    }
+}
+
+/* START GALOIS LFSR */
+#[derive(Copy, Clone, Debug)]
 pub struct PrngCtxGaloisLsfw {
     pub value : u32
 }
 
+impl PrngCtxGaloisLsfw {
+    // A zero value is a fixed point of the LFSR; use a non-zero seed
+    pub fn new(seed : u32) -> PrngCtxGaloisLsfw { PrngCtxGaloisLsfw { value:seed } }
+}
+
+impl Prng for PrngCtxGaloisLsfw {
+    fn next_u32(&mut self) -> u32 {
+        return prng_galois_lsfw(self);
+    }
+
+    // Only 32 bits of state; the halves of the seed are folded together so
+    // seeds below 2^32 match prng_seed_galois_lsfw. Zero stays zero forever.
+    fn seed(&mut self, seed : u64) {
+        prng_seed_galois_lsfw(self, (seed ^ (seed >> 32)) as u32);
+    }
+}
+
 pub fn prng_seed_galois_lsfw(ctx : &mut PrngCtxGaloisLsfw, s : u32) {
     ctx.value = s;
 }
@@ -17,11 +82,186 @@ pub fn prng_galois_lsfw(ctx : &mut PrngCtxGaloisLsfw) -> u32 {
 
 pub fn prng_galois_lsfw_int_minmax(ctx : &mut PrngCtxGaloisLsfw, min : i32,
                                    max : i32) -> i32 {
-    use std::u32;
+    return u32_to_minmax(prng_galois_lsfw(ctx), min, max);
+}
+/* END GALOIS LFSR */
+
+/* START SPLITMIX64 */
+// Port of splitmix64 from logic.c. Any seed, including zero, is fine.
+#[derive(Copy, Clone, Debug)]
+pub struct PrngCtxSplitMix64 {
+    pub value : u64
+}
+
+impl PrngCtxSplitMix64 {
+    pub fn new(seed : u64) -> PrngCtxSplitMix64 { PrngCtxSplitMix64 { value:seed } }
+}
+
+impl Prng for PrngCtxSplitMix64 {
+    fn next_u32(&mut self) -> u32 {
+        return (prng_splitmix64(self) >> 32) as u32;
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        return prng_splitmix64(self);
+    }
+
+    fn seed(&mut self, seed : u64) {
+        prng_seed_splitmix64(self, seed);
+    }
+}
+
+pub fn prng_seed_splitmix64(ctx : &mut PrngCtxSplitMix64, s : u64) {
+    ctx.value = s;
+}
+
+pub fn prng_splitmix64(ctx : &mut PrngCtxSplitMix64) -> u64 {
+    ctx.value = ctx.value.wrapping_add(0x9E3779B97F4A7C15);
+    let mut z = ctx.value;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    return z ^ (z >> 31);
+}
+/* END SPLITMIX64 */
+
+/* START XOSHIRO256** */
+// xoshiro256** (Blackman & Vigna). The state must not be all zero; seeding
+// goes through splitmix64 as the authors recommend, which never produces that.
+#[derive(Copy, Clone, Debug)]
+pub struct PrngCtxXoshiro256ss {
+    pub s : [u64; 4]
+}
+
+impl PrngCtxXoshiro256ss {
+    pub fn new(seed : u64) -> PrngCtxXoshiro256ss {
+        let mut ctx = PrngCtxXoshiro256ss { s:[0; 4] };
+        prng_seed_xoshiro256ss(&mut ctx, seed);
+        return ctx;
+    }
+}
+
+impl Prng for PrngCtxXoshiro256ss {
+    fn next_u32(&mut self) -> u32 {
+        return (prng_xoshiro256ss(self) >> 32) as u32;
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        return prng_xoshiro256ss(self);
+    }
+
+    fn seed(&mut self, seed : u64) {
+        prng_seed_xoshiro256ss(self, seed);
+    }
+}
+
+pub fn prng_seed_xoshiro256ss(ctx : &mut PrngCtxXoshiro256ss, s : u64) {
+    let mut sm = PrngCtxSplitMix64::new(s);
+    for word in ctx.s.iter_mut() {
+        *word = prng_splitmix64(&mut sm);
+    }
+}
+
+pub fn prng_xoshiro256ss(ctx : &mut PrngCtxXoshiro256ss) -> u64 {
+    let s = &mut ctx.s;
+    let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+    let t = s[1] << 17;
+    s[2] ^= s[0];
+    s[3] ^= s[1];
+    s[1] ^= s[2];
+    s[0] ^= s[3];
+    s[2] ^= t;
+    s[3] = s[3].rotate_left(45);
+    return result;
+}
+/* END XOSHIRO256** */
+
+fn u32_to_minmax(u : u32, min : i32, max : i32) -> i32 {
     const INV_INT_MAX : f32 = 1.0 / ((u32::MAX) as f32);
-    let f : f32 = (prng_galois_lsfw(ctx) as f32) * INV_INT_MAX;
+    let f : f32 = (u as f32) * INV_INT_MAX;
     let frange : f32 = (max-min) as f32;
     return ((f * frange + 0.5) as i32) + min;
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prng_galois_lsfw_trait_test() {
+        // Same sequence as the free functions (see prng_galois_lsfw_test)
+        let mut ctx = PrngCtxGaloisLsfw::new(0);
+        ctx.seed(1);
+        let expected_values : [u32 ; 4] = [2149580803, 3224371202, 1612185601, 2955673603];
+        for expected in expected_values.iter() {
+            assert_eq!(*expected, ctx.next_u32());
+        }
+        ctx.seed(1);
+        assert_eq!((2149580803u64 << 32) | 3224371202, ctx.next_u64());
+        // High and low seed halves are folded together
+        ctx.seed((5u64 << 32) | 4);
+        assert_eq!(1, ctx.value);
+    }
+
+    #[test]
+    fn prng_splitmix64_test() {
+        // Reference values from splitmix64 in logic.c
+        let mut ctx = PrngCtxSplitMix64::new(1234567);
+        let expected_values : [u64 ; 5] = [6457827717110365317, 3203168211198807973,
+            9817491932198370423, 4593380528125082431, 16408922859458223821];
+        for expected in expected_values.iter() {
+            assert_eq!(*expected, prng_splitmix64(&mut ctx));
+        }
+        ctx.seed(0);
+        let expected_values : [u64 ; 5] = [16294208416658607535, 7960286522194355700,
+            487617019471545679, 17909611376780542444, 1961750202426094747];
+        for expected in expected_values.iter() {
+            assert_eq!(*expected, ctx.next_u64());
+        }
+        ctx.seed(1234567);
+        assert_eq!((6457827717110365317u64 >> 32) as u32, ctx.next_u32());
+    }
+
+    #[test]
+    fn prng_xoshiro256ss_test() {
+        // Reference values from the authors' C implementation
+        let mut ctx = PrngCtxXoshiro256ss { s:[1, 2, 3, 4] };
+        let expected_values : [u64 ; 6] = [11520, 0, 1509978240, 1215971899390074240,
+            1216172134540287360, 607988272756665600];
+        for expected in expected_values.iter() {
+            assert_eq!(*expected, prng_xoshiro256ss(&mut ctx));
+        }
+        // Seeded through splitmix64
+        ctx.seed(42);
+        let expected_values : [u64 ; 6] = [1546998764402558742, 6990951692964543102,
+            12544586762248559009, 17057574109182124193, 18295552978065317476,
+            14199186830065750584];
+        for expected in expected_values.iter() {
+            assert_eq!(*expected, ctx.next_u64());
+        }
+    }
+
+    fn check_bounded<R : Prng>(rng : &mut R) {
+        let mut hits = [0u32; 7];
+        for _ in 0..7000 {
+            let v = rng.next_bounded(7);
+            hits[v as usize] += 1;
+            let r = rng.int_range(-3, 3);
+            assert!((-3..=3).contains(&r));
+        }
+        // Roughly uniform; each bucket expects 1000
+        for h in hits.iter() {
+            assert!(*h > 800 && *h < 1200, "{:?}", hits);
+        }
+        assert_eq!(5, rng.int_range(5, 5));
+        rng.int_range(i32::MIN, i32::MAX);
+    }
+
+    #[test]
+    fn prng_bounded_test() {
+        check_bounded(&mut PrngCtxGaloisLsfw::new(34));
+        check_bounded(&mut PrngCtxSplitMix64::new(34));
+        check_bounded(&mut PrngCtxXoshiro256ss::new(34));
+    }
+}
+
 /* vim: set ts=4 sts=4 sw=4 et : */
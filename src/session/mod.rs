@@ -0,0 +1,297 @@
+/*
+    Copyright (C) 2016  Erik Beran
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use logic::*;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionError {
+    // Flip index outside the stack
+    InvalidMove(Move),
+    // jump_to past the end of the history
+    PositionOutOfRange { position : usize, len : usize },
+    UnknownBranch(String),
+    BranchExists(String),
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SessionError::InvalidMove(mv) =>
+                write!(f, "flip at index {} ({:?}) is outside the stack", mv.index, mv.side),
+            SessionError::PositionOutOfRange { position, len } =>
+                write!(f, "position {} is past the end of the history ({} moves)", position, len),
+            SessionError::UnknownBranch(ref name) => write!(f, "no branch named {:?}", name),
+            SessionError::BranchExists(ref name) => write!(f, "branch {:?} already exists", name),
+        }
+    }
+}
+
+impl Error for SessionError {}
+
+// A stack being played, with the full line of moves made on it.
+//
+// The first `position()` moves of the line are applied to the stack; the rest
+// is the redo tail. Because every flip is its own inverse, undo and redo just
+// re-apply recorded moves and the start state never needs to be stored.
+// Alternative lines can be kept as named branches.
+pub struct GameSession<const N : usize = MAX_SLICES, T = ()> {
+    stack : SliceStackN<N, T>,
+    line : Vec<Move>,
+    position : usize,
+    branches : BTreeMap<String, Vec<Move>>,
+}
+
+impl<const N : usize, T> GameSession<N, T> {
+    pub fn new(stack : SliceStackN<N, T>) -> GameSession<N, T> {
+        GameSession { stack, line:Vec::new(), position:0, branches:BTreeMap::new() }
+    }
+
+    pub fn stack(&self) -> &SliceStackN<N, T> { &self.stack }
+
+    // Stack payloads can change freely; the slice colors only through moves
+    pub fn objects_mut(&mut self) -> &mut [T] { self.stack.objects_mut() }
+
+    // Number of moves currently applied
+    pub fn position(&self) -> usize { self.position }
+
+    // The whole current line, including the redo tail
+    pub fn moves(&self) -> &[Move] { &self.line }
+
+    // The moves that led to the current stack
+    pub fn applied(&self) -> &[Move] { &self.line[0..self.position] }
+
+    pub fn can_undo(&self) -> bool { self.position > 0 }
+
+    pub fn can_redo(&self) -> bool { self.position < self.line.len() }
+
+    // Same arguments as SliceStackN::flip
+    pub fn flip(&mut self, index : i32, direction : i32) -> Result<(), SessionError> {
+        return self.play(Move::new(index, Side::from_direction(direction)));
+    }
+
+    // Applies a move and records it. Any redo tail is dropped.
+    pub fn play(&mut self, mv : Move) -> Result<(), SessionError> {
+        if !mv.is_legal(self.stack.count()) {
+            return Err(SessionError::InvalidMove(mv));
+        }
+        mv.apply(&mut self.stack);
+        self.line.truncate(self.position);
+        self.line.push(mv);
+        self.position += 1;
+        return Ok(());
+    }
+
+    // Like play, but first keeps the current line (redo tail included) as
+    // the branch `name`.
+    pub fn play_branching(&mut self, mv : Move, name : &str) -> Result<(), SessionError> {
+        if !mv.is_legal(self.stack.count()) {
+            return Err(SessionError::InvalidMove(mv));
+        }
+        self.save_branch(name)?;
+        return self.play(mv);
+    }
+
+    pub fn undo(&mut self) -> Option<Move> {
+        if !self.can_undo() {
+            return None;
+        }
+        self.position -= 1;
+        let mv = self.line[self.position];
+        mv.inverse().apply(&mut self.stack);
+        return Some(mv);
+    }
+
+    pub fn redo(&mut self) -> Option<Move> {
+        if !self.can_redo() {
+            return None;
+        }
+        let mv = self.line[self.position];
+        mv.apply(&mut self.stack);
+        self.position += 1;
+        return Some(mv);
+    }
+
+    // Moves to any point of the current line; 0 is the start state
+    pub fn jump_to(&mut self, position : usize) -> Result<(), SessionError> {
+        if position > self.line.len() {
+            return Err(SessionError::PositionOutOfRange { position, len:self.line.len() });
+        }
+        while self.position > position {
+            self.undo();
+        }
+        while self.position < position {
+            self.redo();
+        }
+        return Ok(());
+    }
+
+    // Stores the current line (redo tail included) under `name`
+    pub fn save_branch(&mut self, name : &str) -> Result<(), SessionError> {
+        if self.branches.contains_key(name) {
+            return Err(SessionError::BranchExists(name.to_string()));
+        }
+        self.branches.insert(name.to_string(), self.line.clone());
+        return Ok(());
+    }
+
+    // Replaces the current line with a saved branch and moves to its end.
+    // Only the moves after the shared prefix are undone and replayed. The
+    // branch stays saved; save the current line first to keep it.
+    pub fn switch_branch(&mut self, name : &str) -> Result<(), SessionError> {
+        let branch = match self.branches.get(name) {
+            Some(branch) => branch.clone(),
+            None => return Err(SessionError::UnknownBranch(name.to_string())),
+        };
+        let shared = self.applied().iter().zip(branch.iter())
+            .take_while(|&(a, b)| a == b).count();
+        // Can't fail; shared <= position <= line length
+        let _ = self.jump_to(shared);
+        self.line = branch;
+        let end = self.line.len();
+        let _ = self.jump_to(end);
+        return Ok(());
+    }
+
+    pub fn delete_branch(&mut self, name : &str) -> Result<Vec<Move>, SessionError> {
+        return self.branches.remove(name).ok_or(SessionError::UnknownBranch(name.to_string()));
+    }
+
+    pub fn branch(&self, name : &str) -> Option<&[Move]> {
+        return self.branches.get(name).map(|b| &b[..]);
+    }
+
+    // Branch names in sorted order
+    pub fn branch_names(&self) -> Vec<&str> {
+        return self.branches.keys().map(|k| &k[..]).collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start() -> SliceStack {
+        return SliceStack::from_slices(&[0, 1, 2, 1, 3, 0], 4).unwrap();
+    }
+
+    #[test]
+    fn session_undo_redo_test() {
+        let mut session = GameSession::new(start());
+        assert!(!session.can_undo());
+        assert_eq!(None, session.undo());
+
+        session.flip(3, -1).unwrap();
+        session.flip(2, 1).unwrap();
+        let after_two = *session.stack();
+        assert_eq!(2, session.position());
+        assert_eq!(&[Move::new(3, Side::Left), Move::new(2, Side::Right)], session.moves());
+
+        assert_eq!(Some(Move::new(2, Side::Right)), session.undo());
+        assert_eq!(Some(Move::new(3, Side::Left)), session.undo());
+        assert_eq!(start().slices(), session.stack().slices());
+        assert!(session.can_redo());
+        assert_eq!(2, session.moves().len());
+        assert_eq!(0, session.applied().len());
+
+        session.redo();
+        session.redo();
+        assert_eq!(None, session.redo());
+        assert_eq!(after_two.slices(), session.stack().slices());
+
+        assert_eq!(Err(SessionError::InvalidMove(Move::new(6, Side::Left))),
+                   session.play(Move::new(6, Side::Left)));
+        assert_eq!(Err(SessionError::InvalidMove(Move::new(-1, Side::Right))),
+                   session.flip(-1, 1));
+        assert_eq!(2, session.position());
+    }
+
+    #[test]
+    fn session_jump_test() {
+        let mut session = GameSession::new(start());
+        let moves = [Move::new(3, Side::Left), Move::new(2, Side::Right),
+                     Move::new(4, Side::Left), Move::new(1, Side::Right)];
+        let mut states = vec![start()];
+        for mv in moves.iter() {
+            session.play(*mv).unwrap();
+            states.push(*session.stack());
+        }
+        for &p in [2, 0, 4, 1, 3].iter() {
+            session.jump_to(p).unwrap();
+            assert_eq!(p, session.position());
+            assert_eq!(states[p].slices(), session.stack().slices());
+        }
+        assert_eq!(Err(SessionError::PositionOutOfRange { position:5, len:4 }),
+                   session.jump_to(5));
+    }
+
+    #[test]
+    fn session_branch_test() {
+        let mut session = GameSession::new(start());
+        session.flip(3, -1).unwrap();
+        session.flip(2, 1).unwrap();
+        let main_end = *session.stack();
+        session.undo();
+
+        // A plain move drops the redo tail
+        session.play(Move::new(4, Side::Left)).unwrap();
+        assert_eq!(2, session.moves().len());
+        assert_eq!(Move::new(4, Side::Left), session.moves()[1]);
+
+        // A branching move keeps the old line around
+        session.undo();
+        session.play_branching(Move::new(1, Side::Right), "alt").unwrap();
+        assert_eq!(vec!["alt"], session.branch_names());
+        assert_eq!(Some(&[Move::new(3, Side::Left), Move::new(4, Side::Left)][..]),
+                   session.branch("alt"));
+        assert_eq!(Err(SessionError::BranchExists("alt".to_string())),
+                   session.play_branching(Move::new(1, Side::Right), "alt"));
+
+        // Switching rewinds to the shared prefix and replays the branch
+        session.save_branch("main").unwrap();
+        session.switch_branch("alt").unwrap();
+        assert_eq!(2, session.position());
+        let mut expected = start();
+        Move::new(3, Side::Left).apply(&mut expected);
+        Move::new(4, Side::Left).apply(&mut expected);
+        assert_eq!(expected.slices(), session.stack().slices());
+
+        session.undo();
+        session.play(Move::new(2, Side::Right)).unwrap();
+        assert_eq!(main_end.slices(), session.stack().slices());
+
+        assert_eq!(Err(SessionError::UnknownBranch("gone".to_string())),
+                   session.switch_branch("gone"));
+        assert!(session.delete_branch("alt").is_ok());
+        assert_eq!(vec!["main"], session.branch_names());
+    }
+
+    #[test]
+    fn session_payload_test() {
+        let stack : SliceStack<char> = start().with_objects("abcdef".chars());
+        let mut session = GameSession::new(stack);
+        session.flip(2, -1).unwrap();
+        assert_eq!(&['c', 'b', 'a', 'd', 'e', 'f'], session.stack().objects());
+        session.objects_mut()[0] = 'z';
+        session.undo();
+        assert_eq!(&['a', 'b', 'z', 'd', 'e', 'f'], session.stack().objects());
+    }
+}
+
+/* vim: set ts=4 sts=4 sw=4 et : */
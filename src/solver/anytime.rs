@@ -0,0 +1,100 @@
+/*
+    Copyright (C) 2016  Erik Beran
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use logic::*;
+use solver::{Goal, GreedySolver, IdaSolver, Limits, Solution, Solver, Status};
+
+// Always has an answer: the greedy solution first, then `solver` within the
+// limits, keeping whichever is shorter. Solution::is_optimal() says whether
+// the answer is proven shortest, either by `solver` finishing or by the
+// greedy solution matching Goal::lower_bound.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Anytime<S = IdaSolver> {
+    pub solver : S,
+    pub fallback : GreedySolver,
+}
+
+impl<S> Anytime<S> {
+    pub fn new(solver : S) -> Anytime<S> {
+        Anytime { solver, fallback:GreedySolver::default() }
+    }
+}
+
+impl<const N : usize, S : Solver<N>> Solver<N> for Anytime<S> {
+    // Without limits this is just `solver`, unless it gives up
+    fn solve_goal(&self, s : &SliceStackN<N>, goal : &Goal, limits : &Limits) -> Solution {
+        let mut best = self.fallback.solve_goal(s, goal, limits);
+        if !best.is_solved() {
+            // Unreachable goal
+            return best;
+        }
+        if best.len() as i32 <= goal.lower_bound(s) {
+            best.status = Status::Optimal;
+            return best;
+        }
+        let searched = self.solver.solve_goal(s, goal, limits);
+        let nodes_expanded = best.nodes_expanded + searched.nodes_expanded;
+        if searched.is_solved() && (searched.len() < best.len() || searched.is_optimal()) {
+            best = searched;
+        }
+        best.nodes_expanded = nodes_expanded;
+        return best;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use logic::testing::*;
+    use prng::*;
+    use solver::{lower_bound, BeamSolver, CancelToken};
+
+    #[test]
+    fn anytime_test() {
+        let anytime = Anytime::new(IdaSolver::new());
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        for s in random_stacks(&mut PrngCtxGaloisLsfw::new(34), 20, MAX_SLICES as i32, 8) {
+            let greedy = GreedySolver::default().solve(&s);
+
+            // Plenty of time: optimal
+            let solution = anytime.solve(&s);
+            assert!(solution.is_optimal());
+            assert!(solution.len() <= greedy.len());
+
+            // Cancelled before starting: the greedy answer
+            let limits = Limits::none().with_cancel(cancel.clone());
+            let fallback = anytime.solve_with_limits(&s, &limits);
+            assert_eq!(greedy.moves, fallback.moves);
+            assert!(fallback.is_solved());
+            assert_eq!(fallback.len() as i32 == lower_bound(&s), fallback.is_optimal());
+        }
+    }
+
+    #[test]
+    fn anytime_beam_test() {
+        // Beam answers aren't proven, so only greedy matching the bound is
+        let anytime = Anytime::new(BeamSolver::default());
+        let s : SliceStack = "ABACBCDBD".parse().unwrap();
+        let solution = anytime.solve_with_limits(&s, &Limits::none().with_max_nodes(1));
+        assert!(solution.apply(&s).iscomplete());
+        let done : SliceStack = "AABB".parse().unwrap();
+        assert!(anytime.solve(&done).is_optimal());
+    }
+}
+
+/* vim: set ts=4 sts=4 sw=4 et : */
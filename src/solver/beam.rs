@@ -0,0 +1,216 @@
+/*
+    Copyright (C) 2016  Erik Beran
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use logic::*;
+use solver::{Goal, Limits, Solution, Solver, Status};
+use std::collections::HashSet;
+
+pub const DEFAULT_BEAM_WIDTH : usize = 64;
+
+// Scores a state for the beam; lower is closer to meeting `goal`, which is
+// always resolved (Goal::Any or Goal::Exact)
+pub trait Evaluation {
+    fn evaluate<const N : usize>(&self, s : &SliceStackN<N>, goal : &Goal) -> i32;
+}
+
+// SliceStackN::fragmentation; Goal::lower_bound for a target, since runs in
+// the wrong order look just as good to fragmentation
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Fragmentation;
+
+impl Evaluation for Fragmentation {
+    fn evaluate<const N : usize>(&self, s : &SliceStackN<N>, goal : &Goal) -> i32 {
+        if goal.is_any() {
+            return s.fragmentation();
+        }
+        return goal.lower_bound(s);
+    }
+}
+
+// Goal::lower_bound (solver::lower_bound for Goal::Any, which also counts
+// whether a joining move is available)
+#[derive(Debug, Copy, Clone, Default)]
+pub struct LowerBound;
+
+impl Evaluation for LowerBound {
+    fn evaluate<const N : usize>(&self, s : &SliceStackN<N>, goal : &Goal) -> i32 {
+        return goal.lower_bound(s);
+    }
+}
+
+// Breadth-first, but each depth keeps only the `width` best states by
+// `evaluation` (ties keep generation order). States seen at an earlier depth
+// are dropped. The beam itself is width * max_depth states, but the set of
+// seen states keeps every child generated, up to width times the number of
+// flips more per depth. The result is usually shorter than greedy's, but not
+// guaranteed optimal, and the search can come up empty (Status::Unsolved) if
+// the beam is too narrow.
+#[derive(Debug, Copy, Clone)]
+pub struct BeamSolver<E = Fragmentation> {
+    pub width : usize,
+    // Greedy never needs more than two flips per slice, so by default a beam
+    // deeper than 2 * count has lost its way
+    pub max_depth : Option<usize>,
+    pub evaluation : E,
+}
+
+impl Default for BeamSolver {
+    fn default() -> BeamSolver { BeamSolver::new(DEFAULT_BEAM_WIDTH) }
+}
+
+impl BeamSolver {
+    pub fn new(width : usize) -> BeamSolver {
+        BeamSolver::with_evaluation(width, Fragmentation)
+    }
+}
+
+impl<E : Evaluation> BeamSolver<E> {
+    pub fn with_evaluation(width : usize, evaluation : E) -> BeamSolver<E> {
+        BeamSolver { width, max_depth:None, evaluation }
+    }
+}
+
+// A state in the beam and how it was reached from the previous level
+struct Node<const N : usize> {
+    stack : SliceStackN<N>,
+    parent : usize,
+    mv : Move,
+}
+
+impl<const N : usize, E : Evaluation> Solver<N> for BeamSolver<E> {
+    fn solve_goal(&self, start : &SliceStackN<N>, goal : &Goal, limits : &Limits) -> Solution {
+        let start = start.without_objects();
+        if !goal.is_reachable(&start) {
+            return Solution::unsolved(0);
+        }
+        let goal = goal.resolve(&start);
+        if goal.is_complete(&start) {
+            return Solution { moves:Vec::new(), status:Status::Optimal, nodes_expanded:0 };
+        }
+
+        let mut seen : HashSet<SliceStackN<N>> = HashSet::new();
+        seen.insert(start);
+        // levels[d] is the beam after d moves; the start's parent and move
+        // are never read
+        let mut levels : Vec<Vec<Node<N>>> = vec![vec![Node { stack:start, parent:0,
+            mv:Move::new(0, Side::Left) }]];
+        let mut nodes_expanded : u64 = 0;
+
+        let max_depth = self.max_depth.unwrap_or(2 * start.count() as usize);
+        while levels.len() <= max_depth {
+            let mut candidates : Vec<(i32, Node<N>)> = Vec::new();
+            for (i, node) in levels[levels.len() - 1].iter().enumerate() {
+                if limits.exceeded(nodes_expanded) {
+                    return Solution::unsolved(nodes_expanded);
+                }
+                nodes_expanded += 1;
+                for mv in Move::all(node.stack.count()) {
+                    let mut next = node.stack;
+                    mv.apply(&mut next);
+                    if !seen.insert(next) {
+                        continue;
+                    }
+                    if goal.is_complete(&next) {
+                        let mut moves = vec![mv];
+                        moves.extend(path_to(&levels, i));
+                        moves.reverse();
+                        return Solution { moves, status:Status::Solved, nodes_expanded };
+                    }
+                    candidates.push((self.evaluation.evaluate(&next, &goal),
+                                     Node { stack:next, parent:i, mv }));
+                }
+            }
+            if candidates.is_empty() {
+                break;
+            }
+            candidates.sort_by_key(|c| c.0);
+            candidates.truncate(self.width);
+            levels.push(candidates.into_iter().map(|c| c.1).collect());
+        }
+        return Solution::unsolved(nodes_expanded);
+    }
+}
+
+// Moves from the start to node `index` of the last level, last move first
+fn path_to<const N : usize>(levels : &[Vec<Node<N>>], mut index : usize) -> Vec<Move> {
+    let mut moves = Vec::new();
+    for level in levels[1..].iter().rev() {
+        let node = &level[index];
+        moves.push(node.mv);
+        index = node.parent;
+    }
+    return moves;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use logic::testing::*;
+    use prng::*;
+    use solver::{GreedySolver, IdaSolver};
+
+    #[test]
+    fn beam_known_test() {
+        let beam = BeamSolver::default();
+        let done : SliceStack = "AABBC".parse().unwrap();
+        assert!(beam.solve(&done).is_optimal());
+
+        let s : SliceStack = "ABCBD".parse().unwrap();
+        let solution = beam.solve(&s);
+        assert_eq!(Status::Solved, solution.status);
+        assert_eq!(2, solution.len());
+        assert!(solution.apply(&s).iscomplete());
+
+        // Too shallow to finish
+        let beam = BeamSolver { max_depth:Some(1), ..BeamSolver::default() };
+        assert!(!beam.solve(&s).is_solved());
+    }
+
+    #[test]
+    fn beam_large_stack_test() {
+        // Deeper than 2 * MAX_SLICES; the default depth follows the stack
+        let mut rng = PrngCtxSplitMix64::new(14);
+        let mut s = SliceStackN::<64>::new();
+        s.init_with(&mut rng, 64, 16);
+        let greedy = GreedySolver::default().solve(&s);
+        assert!(greedy.len() > 2 * MAX_SLICES);
+        let solution = BeamSolver::new(4).solve(&s);
+        assert!(solution.apply(&s).iscomplete());
+    }
+
+    fn check_vs_greedy<E : Evaluation>(beam : &BeamSolver<E>) {
+        let greedy = GreedySolver::default();
+        let ida = IdaSolver::new();
+        let (mut greedy_total, mut beam_total) = (0, 0);
+        for s in random_stacks(&mut PrngCtxGaloisLsfw::new(34), 20, MAX_SLICES as i32, 8) {
+            let solution = beam.solve(&s);
+            assert!(solution.apply(&s).iscomplete());
+            assert!(solution.len() >= ida.solve(&s).len());
+            greedy_total += greedy.solve(&s).len();
+            beam_total += solution.len();
+        }
+        assert!(beam_total < greedy_total, "beam {} greedy {}", beam_total, greedy_total);
+    }
+
+    #[test]
+    fn beam_vs_greedy_test() {
+        check_vs_greedy(&BeamSolver::default());
+        check_vs_greedy(&BeamSolver::with_evaluation(16, LowerBound));
+    }
+}
+
+/* vim: set ts=4 sts=4 sw=4 et : */
@@ -0,0 +1,180 @@
+/*
+    Copyright (C) 2016  Erik Beran
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use logic::*;
+use solver::{Goal, Limits, Solution, Solver, Status};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+
+// Roughly 100 bytes per stored 16 slice state (key, parent and table
+// overhead), so the default stays in the hundreds of megabytes.
+pub const DEFAULT_MAX_STATES : usize = 4 * 1024 * 1024;
+
+// state -> (previous state, move that led here); None for the start
+type Parents<const N : usize> = HashMap<SliceStackN<N>, Option<(SliceStackN<N>, Move)>>;
+
+// Breadth-first search over flips. Every state reached is remembered, so the
+// first complete state found is at the minimum number of moves. Memory grows
+// with the number of states seen; max_states caps it and the search gives
+// up (Status::Unsolved) rather than exceed it.
+#[derive(Debug, Copy, Clone)]
+pub struct BfsSolver {
+    pub max_states : usize,
+}
+
+impl Default for BfsSolver {
+    fn default() -> BfsSolver { BfsSolver::new() }
+}
+
+impl BfsSolver {
+    pub fn new() -> BfsSolver { BfsSolver { max_states:DEFAULT_MAX_STATES } }
+}
+
+impl<const N : usize> Solver<N> for BfsSolver {
+    fn solve_goal(&self, start : &SliceStackN<N>, goal : &Goal, limits : &Limits) -> Solution {
+        let start = start.without_objects();
+        if !goal.is_reachable(&start) {
+            return Solution::unsolved(0);
+        }
+        let goal = goal.resolve(&start);
+        if goal.is_complete(&start) {
+            return Solution { moves:Vec::new(), status:Status::Optimal, nodes_expanded:0 };
+        }
+
+        let mut parents : Parents<N> = HashMap::new();
+        let mut queue : VecDeque<SliceStackN<N>> = VecDeque::new();
+        let mut nodes_expanded : u64 = 0;
+        parents.insert(start, None);
+        queue.push_back(start);
+
+        while let Some(s) = queue.pop_front() {
+            if limits.exceeded(nodes_expanded) {
+                return Solution::unsolved(nodes_expanded);
+            }
+            nodes_expanded += 1;
+            for mv in Move::all(s.count()) {
+                let mut next = s;
+                mv.apply(&mut next);
+                if let Entry::Vacant(e) = parents.entry(next) {
+                    e.insert(Some((s, mv)));
+                } else {
+                    continue;
+                }
+                if goal.is_complete(&next) {
+                    return Solution { moves:path_to(&parents, next), status:Status::Optimal,
+                        nodes_expanded };
+                }
+                if parents.len() >= self.max_states {
+                    return Solution::unsolved(nodes_expanded);
+                }
+                queue.push_back(next);
+            }
+        }
+        // Every reachable goal can be met, so this only happens for stacks
+        // that can't be flipped at all
+        return Solution::unsolved(nodes_expanded);
+    }
+}
+
+fn path_to<const N : usize>(parents : &Parents<N>, end : SliceStackN<N>) -> Vec<Move> {
+    let mut moves = Vec::new();
+    let mut s = end;
+    while let Some(&Some((prev, mv))) = parents.get(&s) {
+        moves.push(mv);
+        s = prev;
+    }
+    moves.reverse();
+    return moves;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use logic::testing::*;
+    use prng::*;
+
+    // Whether some sequence of at most `depth` flips completes the stack
+    fn solvable_within(s : &SliceStack, depth : usize) -> bool {
+        if s.iscomplete() {
+            return true;
+        }
+        if depth == 0 {
+            return false;
+        }
+        for mv in Move::all(s.count()) {
+            let mut next = *s;
+            mv.apply(&mut next);
+            if solvable_within(&next, depth - 1) {
+                return true;
+            }
+        }
+        return false;
+    }
+
+    #[test]
+    fn bfs_known_test() {
+        let solver = BfsSolver::new();
+        let done : SliceStack = "AABBC".parse().unwrap();
+        let solution = solver.solve(&done);
+        assert!(solution.is_optimal());
+        assert!(solution.is_empty());
+
+        // One flip joins the A's
+        let s : SliceStack = "ABA".parse().unwrap();
+        let solution = solver.solve(&s);
+        assert_eq!(1, solution.len());
+        assert!(solution.apply(&s).iscomplete());
+
+        // No joining move at the edges; needs a setup flip first
+        let s : SliceStack = "ABCBD".parse().unwrap();
+        let solution = solver.solve(&s);
+        assert_eq!(Status::Optimal, solution.status);
+        assert_eq!(2, solution.len());
+        assert!(solution.apply(&s).iscomplete());
+        assert!(solution.nodes_expanded > 0);
+    }
+
+    #[test]
+    fn bfs_matches_brute_force_test() {
+        let solver = BfsSolver::new();
+        for s in random_stacks(&mut PrngCtxSplitMix64::new(11), 40, 7, 3) {
+            let solution = solver.solve(&s);
+            assert!(solution.is_optimal());
+            assert!(solution.apply(&s).iscomplete());
+            let len = solution.len();
+            assert!(solvable_within(&s, len));
+            assert!(len == 0 || !solvable_within(&s, len - 1));
+        }
+    }
+
+    #[test]
+    fn bfs_memory_limit_test() {
+        let s : SliceStack = "ABCDEFGHABCDEFGH".parse().unwrap();
+        let solver = BfsSolver { max_states:1000 };
+        let solution = solver.solve(&s);
+        assert_eq!(Status::Unsolved, solution.status);
+        assert!(solution.is_empty());
+        assert!(solution.nodes_expanded > 0);
+        assert!(solution.nodes_expanded < 1000);
+
+        let solution = BfsSolver::new().solve_with_limits(&s, &Limits::none().with_max_nodes(50));
+        assert_eq!(Status::Unsolved, solution.status);
+        assert_eq!(50, solution.nodes_expanded);
+    }
+}
+
+/* vim: set ts=4 sts=4 sw=4 et : */
@@ -0,0 +1,270 @@
+/*
+    Copyright (C) 2016  Erik Beran
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use logic::*;
+use solver::lower_bound;
+
+// What counts as solved
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Goal {
+    // Each color contiguous, in any order (iscomplete)
+    #[default]
+    Any,
+    // Each color contiguous, the runs in this order from the left edge.
+    // Colors the stack doesn't have are skipped; a stack with a color that
+    // isn't listed can't reach the goal.
+    ColorOrder(Vec<SliceType>),
+    // Exactly these slices
+    Exact(Vec<SliceType>),
+}
+
+impl Goal {
+    // Colors 0, 1, ... type_count-1 from the left edge
+    pub fn sorted(type_count : i32) -> Goal {
+        return Goal::ColorOrder((0..type_count).map(|t| t as SliceType).collect());
+    }
+
+    pub fn exact<const N : usize, T>(target : &SliceStackN<N, T>) -> Goal {
+        return Goal::Exact(target.slices().to_vec());
+    }
+
+    // The same goal for stacks reached from `start`. Flips don't change how
+    // many slices of each color there are, so a color order becomes the one
+    // exact stack with those runs; solvers call this once up front.
+    pub fn resolve<const N : usize, T>(&self, start : &SliceStackN<N, T>) -> Goal {
+        match *self {
+            Goal::ColorOrder(ref order) => {
+                let mut target = Vec::new();
+                for c in order.iter() {
+                    for t in start.slices().iter() {
+                        if t == c {
+                            target.push(*c);
+                        }
+                    }
+                }
+                return Goal::Exact(target);
+            }
+            _ => return self.clone(),
+        }
+    }
+
+    pub fn is_any(&self) -> bool { *self == Goal::Any }
+
+    pub fn is_complete<const N : usize, T>(&self, s : &SliceStackN<N, T>) -> bool {
+        match *self {
+            Goal::Any => return s.iscomplete(),
+            Goal::ColorOrder(_) => return self.resolve(s).is_complete(s),
+            Goal::Exact(ref target) => return s.slices() == &target[..],
+        }
+    }
+
+    // Whether some sequence of flips gets from `s` to the goal: the target
+    // has to be a rearrangement of the stack
+    pub fn is_reachable<const N : usize, T>(&self, s : &SliceStackN<N, T>) -> bool {
+        match self.resolve(s) {
+            Goal::Exact(target) => {
+                let mut have = s.slices().to_vec();
+                let mut want = target;
+                have.sort_unstable();
+                want.sort_unstable();
+                return have == want;
+            }
+            _ => return true,
+        }
+    }
+
+    // Flips needed at least (solver::lower_bound for Goal::Any).
+    //
+    // For a target, compare the neighbouring pairs {s[i], s[i+1]} of both
+    // stacks as unordered pairs. A flip replaces exactly one pair, so the
+    // number of target pairs the stack is missing is a lower bound. With none
+    // missing the stack can still differ (e.g. be the target reversed), which
+    // takes at least one more flip.
+    pub fn lower_bound<const N : usize, T>(&self, s : &SliceStackN<N, T>) -> i32 {
+        match *self {
+            Goal::Any => return lower_bound(s),
+            Goal::ColorOrder(_) => return self.resolve(s).lower_bound(s),
+            Goal::Exact(ref target) => {
+                let missing = missing_pairs(s.slices(), target);
+                if missing == 0 && s.slices() != &target[..] {
+                    return 1;
+                }
+                return missing;
+            }
+        }
+    }
+}
+
+fn sorted_pairs(slices : &[SliceType]) -> Vec<(SliceType, SliceType)> {
+    let mut pairs : Vec<(SliceType, SliceType)> = slices.windows(2)
+        .map(|w| if w[0] <= w[1] { (w[0], w[1]) } else { (w[1], w[0]) })
+        .collect();
+    pairs.sort_unstable();
+    return pairs;
+}
+
+// Target pairs without a match in `slices` (as multisets)
+fn missing_pairs(slices : &[SliceType], target : &[SliceType]) -> i32 {
+    let have = sorted_pairs(slices);
+    let want = sorted_pairs(target);
+    let (mut i, mut j) = (0, 0);
+    let mut missing = 0;
+    while j < want.len() {
+        if i < have.len() && have[i] < want[j] {
+            i += 1;
+        } else if i < have.len() && have[i] == want[j] {
+            i += 1;
+            j += 1;
+        } else {
+            missing += 1;
+            j += 1;
+        }
+    }
+    return missing;
+}
+
+// Puts the target together from the right edge using Left flips only: bring
+// the slice for position p to the left edge, then flip it into place, at
+// most two flips per slice. `target` must be reachable.
+pub fn placement_moves<const N : usize>(start : &SliceStackN<N>, target : &[SliceType])
+    -> Vec<Move> {
+    let mut s = *start;
+    let mut moves = Vec::new();
+    for p in (1..target.len()).rev() {
+        if s.slices()[p] == target[p] {
+            continue;
+        }
+        if s.slices()[0] != target[p] {
+            // Reachable, so the color is somewhere in 1..p
+            let j = (1..p).find(|j| s.slices()[*j] == target[p]).unwrap();
+            let mv = Move::new(j as i32, Side::Left);
+            mv.apply(&mut s);
+            moves.push(mv);
+        }
+        let mv = Move::new(p as i32, Side::Left);
+        mv.apply(&mut s);
+        moves.push(mv);
+    }
+    return moves;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use logic::testing::*;
+    use prng::*;
+    use solver::{Anytime, BeamSolver, BfsSolver, DistanceTable, GreedySolver, IdaSolver, Limits,
+                 Solver, TableSolver};
+
+    #[test]
+    fn goal_complete_test() {
+        let s = stack("BBAAC");
+        assert!(Goal::Any.is_complete(&s));
+        assert!(!Goal::sorted(3).is_complete(&s));
+        assert!(Goal::ColorOrder(vec![1, 0, 2]).is_complete(&s));
+        assert!(Goal::exact(&s).is_complete(&s));
+        assert_eq!(Goal::Exact(vec![0, 0, 1, 1, 2]), Goal::sorted(3).resolve(&s));
+        // Colors missing from the stack are skipped
+        assert!(Goal::sorted(5).is_complete(&stack("AABBC")));
+        assert!(Goal::Any.resolve(&s).is_any());
+
+        assert!(Goal::sorted(3).is_reachable(&s));
+        assert!(!Goal::ColorOrder(vec![0, 1]).is_reachable(&s));
+        assert!(!Goal::Exact(vec![0, 0, 1, 1, 1]).is_reachable(&s));
+    }
+
+    #[test]
+    fn goal_lower_bound_test() {
+        let goal = Goal::sorted(3);
+        assert_eq!(0, goal.lower_bound(&stack("AABBC")));
+        // Same pairs, turned around
+        assert_eq!(1, goal.lower_bound(&stack("CBBAA")));
+        // Only BC missing
+        assert_eq!(1, goal.lower_bound(&stack("BBAAC")));
+        assert_eq!(3, goal.lower_bound(&stack("BACAB")));
+        assert_eq!(lower_bound(&stack("ABA")), Goal::Any.lower_bound(&stack("ABA")));
+    }
+
+    #[test]
+    fn placement_moves_test() {
+        for s in random_stacks(&mut PrngCtxSplitMix64::new(22), 200, 12, 5) {
+            for goal in [Goal::sorted(5), Goal::ColorOrder(vec![4, 2, 0, 1, 3])].iter() {
+                let target = match goal.resolve(&s) { Goal::Exact(t) => t, _ => unreachable!() };
+                let moves = placement_moves(&s, &target);
+                assert!(moves.len() <= 2 * 12);
+                let mut end = s;
+                for mv in moves.iter() {
+                    mv.apply(&mut end);
+                }
+                assert!(goal.is_complete(&end));
+                assert!(goal.lower_bound(&s) <= moves.len() as i32);
+            }
+        }
+    }
+
+    #[test]
+    fn goal_solvers_test() {
+        let bfs = BfsSolver::new();
+        let ida = IdaSolver::new();
+        let table = TableSolver::new(DistanceTable::build(7, 3).unwrap());
+        for s in random_stacks(&mut PrngCtxSplitMix64::new(7), 30, 7, 3) {
+            for goal in [Goal::sorted(3), Goal::ColorOrder(vec![2, 0, 1])].iter() {
+                let expected = bfs.solve_goal(&s, goal, &Limits::none());
+                let solution = ida.solve_goal(&s, goal, &Limits::none());
+                assert!(expected.is_optimal());
+                assert!(solution.is_optimal());
+                assert_eq!(expected.len(), solution.len());
+                assert!(goal.is_complete(&solution.apply(&s)));
+                let from_table = table.solve_goal(&s, goal, &Limits::none());
+                assert!(from_table.is_optimal());
+                assert_eq!(expected.len(), from_table.len());
+                assert!(goal.is_complete(&from_table.apply(&s)));
+
+                let solvers : Vec<Box<dyn Solver>> = vec![Box::new(GreedySolver::default()),
+                                                          Box::new(BeamSolver::default()),
+                                                          Box::new(Anytime::new(ida))];
+                for solver in solvers.iter() {
+                    let other = solver.solve_goal(&s, goal, &Limits::none());
+                    assert!(goal.is_complete(&other.apply(&s)));
+                    assert!(other.len() >= expected.len());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn goal_unreachable_test() {
+        let s = stack("ABCAB");
+        let table = DistanceTable::build(5, 3).unwrap();
+        let goal = Goal::ColorOrder(vec![0, 1]);
+        let solvers : Vec<Box<dyn Solver>> = vec![Box::new(GreedySolver::default()),
+                                                  Box::new(BfsSolver::new()),
+                                                  Box::new(IdaSolver::new()),
+                                                  Box::new(BeamSolver::default()),
+                                                  Box::new(Anytime::new(IdaSolver::new())),
+                                                  Box::new(TableSolver::new(table))];
+        for solver in solvers.iter() {
+            assert!(!solver.solve_goal(&s, &goal, &Limits::none()).is_solved());
+        }
+        // Already there
+        let done = stack("CBBAA");
+        let goal = Goal::ColorOrder(vec![2, 1, 0]);
+        assert!(IdaSolver::new().solve_goal(&done, &goal, &Limits::none()).is_optimal());
+    }
+}
+
+/* vim: set ts=4 sts=4 sw=4 et : */
@@ -0,0 +1,144 @@
+/*
+    Copyright (C) 2016  Erik Beran
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use logic::*;
+use solver::goal::placement_moves;
+use solver::{Goal, Limits, Solution, Solver, Status};
+
+// Which of the duplicate implementations of the stack operations to use; all
+// of them find the same moves, they only differ in bounds checking.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[allow(clippy::enum_variant_names)]
+pub enum RunSafety {
+    AllSafe,
+    SomeSafe,
+    NoSafe,
+}
+
+// Takes a single joining move whenever there is one, otherwise the first move
+// that sets one up. Fast, but not optimal. Goals other than Goal::Any are
+// put together slice by slice instead (at most two flips per slice).
+#[derive(Debug, Copy, Clone)]
+pub struct GreedySolver {
+    pub run_safety : RunSafety,
+}
+
+impl Default for GreedySolver {
+    fn default() -> GreedySolver { GreedySolver::new(RunSafety::AllSafe) }
+}
+
+impl GreedySolver {
+    pub fn new(run_safety : RunSafety) -> GreedySolver { GreedySolver { run_safety } }
+}
+
+impl<const N : usize> Solver<N> for GreedySolver {
+    fn solve_goal(&self, start : &SliceStackN<N>, goal : &Goal, _limits : &Limits) -> Solution {
+        let mut s = *start;
+        let mut moves = Vec::new();
+        if !goal.is_reachable(start) {
+            return Solution::unsolved(0);
+        }
+        if let Goal::Exact(target) = goal.resolve(start) {
+            moves = placement_moves(start, &target);
+        } else {
+            match self.run_safety {
+                RunSafety::AllSafe => solve_stack_all_safe(&mut s, &mut moves),
+                RunSafety::SomeSafe => solve_stack_some_safe(&mut s, &mut moves),
+                RunSafety::NoSafe => solve_stack_no_safe(&mut s, &mut moves),
+            }
+        }
+        let status = if moves.is_empty() { Status::Optimal } else { Status::Solved };
+        let nodes_expanded = moves.len() as u64;
+        return Solution { moves, status, nodes_expanded };
+    }
+}
+
+fn solve_stack_all_safe<const N : usize>(s : &mut SliceStackN<N>, moves : &mut Vec<Move>) {
+    let search_dir : i32 = 0;
+    while !s.iscomplete() {
+        let mv = s.find_single_joining_move()
+            .or_else(|| s.find_first_double_move(search_dir));
+        // An incomplete stack always has a double move
+        let mv = mv.unwrap();
+        mv.apply(s);
+        moves.push(mv);
+        //search_dir = !search_dir;
+    }
+}
+
+fn solve_stack_some_safe<const N : usize>(s : &mut SliceStackN<N>, moves : &mut Vec<Move>) {
+    let search_dir : i32 = 0;
+    while !s.iscomplete_unsafe() {
+        let mv = s.find_single_joining_move_unchecked()
+            .or_else(|| s.find_first_double_move(search_dir))
+            .unwrap();
+        s.flip_rsslice(mv.index, mv.side.direction());
+        moves.push(mv);
+        //search_dir = !search_dir;
+    }
+}
+
+fn solve_stack_no_safe<const N : usize>(s : &mut SliceStackN<N>, moves : &mut Vec<Move>) {
+    let search_dir : i32 = 0;
+    while !s.iscomplete_unsafe() {
+        let mv = s.find_single_joining_move_rawptr()
+            .or_else(|| s.find_first_double_move_unsafe(search_dir))
+            .unwrap();
+        //s.flip_rsslice(mv.index, mv.side.direction());
+        s.flip_unsafe(mv.index, mv.side.direction());
+        moves.push(mv);
+        //search_dir = !search_dir;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use logic::testing::*;
+    use prng::*;
+    use solver::IdaSolver;
+
+    #[test]
+    fn greedy_variants_test() {
+        let solvers = [GreedySolver::new(RunSafety::AllSafe),
+                       GreedySolver::new(RunSafety::SomeSafe),
+                       GreedySolver::new(RunSafety::NoSafe)];
+        for s in random_stacks(&mut PrngCtxGaloisLsfw::new(34), 200, MAX_SLICES as i32, 6) {
+            let solutions : Vec<Solution> = solvers.iter().map(|g| g.solve(&s)).collect();
+            for solution in solutions.iter() {
+                assert!(solution.is_solved());
+                assert!(solution.apply(&s).iscomplete());
+                assert_eq!(solutions[0].moves, solution.moves);
+            }
+        }
+        let done : SliceStack = "AABBC".parse().unwrap();
+        assert!(GreedySolver::default().solve(&done).is_optimal());
+    }
+
+    #[test]
+    fn solver_trait_object_test() {
+        let solvers : Vec<Box<dyn Solver>> = vec![Box::new(GreedySolver::default()),
+                                                  Box::new(IdaSolver::new())];
+        let s : SliceStack = "ABCBD".parse().unwrap();
+        for solver in solvers.iter() {
+            assert!(solver.solve(&s).apply(&s).iscomplete());
+        }
+        assert_eq!(2, solvers[1].solve(&s).len());
+    }
+}
+
+/* vim: set ts=4 sts=4 sw=4 et : */
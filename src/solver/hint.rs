@@ -0,0 +1,206 @@
+/*
+    Copyright (C) 2016  Erik Beran
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use logic::*;
+use solver::{IdaSolver, Limits, Solver};
+use std::fmt;
+
+// Keeps hints quick on big stacks; past it the hint falls back to the
+// greedy move
+pub const DEFAULT_HINT_MAX_NODES : u64 = 200_000;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HintCategory {
+    // The greedy move, joining two runs of the same color
+    // (find_single_joining_move)
+    Joining,
+    // The greedy move when nothing can be joined: the first half of a double
+    // move (find_first_double_move)
+    Setup,
+    // A move from the optimal search, because the greedy move isn't on any
+    // shortest path
+    OptimalPath,
+}
+
+impl fmt::Display for HintCategory {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            HintCategory::Joining => write!(f, "joining"),
+            HintCategory::Setup => write!(f, "setup"),
+            HintCategory::OptimalPath => write!(f, "optimal path"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hint {
+    pub mv : Move,
+    pub category : HintCategory,
+    // Fragmentation after the move minus before; -1 for a joining move
+    pub fragmentation_delta : i32,
+    // Moves to finish, this one included, when the search proved it
+    pub moves_left : Option<usize>,
+    // For showing to the player
+    pub explanation : String,
+}
+
+// The move to suggest for `s`, or None if it is already complete. The
+// greedy move (joining if there is one, otherwise setup) is preferred as
+// long as it is on a shortest path, since it's the easiest to follow.
+pub fn hint<const N : usize>(s : &SliceStackN<N>) -> Option<Hint> {
+    return hint_with_limits(s, &Limits::none().with_max_nodes(DEFAULT_HINT_MAX_NODES));
+}
+
+// hint() with both optimal searches together held to `limits`
+pub fn hint_with_limits<const N : usize>(s : &SliceStackN<N>, limits : &Limits) -> Option<Hint> {
+    if s.iscomplete() {
+        return None;
+    }
+    // An incomplete stack always has a double move
+    let greedy = s.find_single_joining_move()
+        .or_else(|| s.find_first_double_move(0))
+        .unwrap();
+
+    let mut mv = greedy;
+    let mut moves_left = None;
+    let optimal = IdaSolver::new().solve_with_limits(s, limits);
+    if optimal.is_optimal() {
+        moves_left = Some(optimal.len());
+        let rest = IdaSolver::new().solve_with_limits(&after(s, greedy),
+                                                      &limits.remaining(optimal.nodes_expanded));
+        if !(rest.is_optimal() && rest.len() + 1 == optimal.len()) {
+            mv = optimal.moves[0];
+        }
+    }
+
+    let next = after(s, mv);
+    let fragmentation_delta = next.fragmentation() - s.fragmentation();
+    let category = if mv != greedy {
+        HintCategory::OptimalPath
+    } else if fragmentation_delta < 0 {
+        HintCategory::Joining
+    } else {
+        HintCategory::Setup
+    };
+
+    let mut explanation = if fragmentation_delta < 0 {
+        format!("{} joins two runs of {}.", mv, color_name(joined_color(s, mv)))
+    } else if let Some(join) = next.find_single_joining_move() {
+        format!("{} doesn't join anything yet, but afterwards {} joins two runs of {}.",
+                mv, join, color_name(joined_color(&next, join)))
+    } else {
+        format!("{} doesn't join anything yet.", mv)
+    };
+    if category == HintCategory::OptimalPath {
+        explanation.push_str(&format!(" It looks less obvious than {}, but it's quicker overall.",
+                                      greedy));
+    }
+    match moves_left {
+        Some(1) => explanation.push_str(" That completes the stack."),
+        Some(n) => explanation.push_str(&format!(" The stack can be finished in {} moves.", n)),
+        None => {}
+    }
+    return Some(Hint { mv, category, fragmentation_delta, moves_left, explanation });
+}
+
+fn after<const N : usize>(s : &SliceStackN<N>, mv : Move) -> SliceStackN<N> {
+    let mut next = *s;
+    mv.apply(&mut next);
+    return next;
+}
+
+// Color of the slice that ends up next to the flip point. Only meaningful
+// for joining moves, where both sides of the new pair have it.
+fn joined_color<const N : usize>(s : &SliceStackN<N>, mv : Move) -> SliceType {
+    let slices = s.slices();
+    match mv.side {
+        Side::Left => return slices[0],
+        Side::Right => return slices[slices.len() - 1],
+    }
+}
+
+// The letter the stack notation uses
+fn color_name(t : SliceType) -> String {
+    if t < 26 {
+        return format!("{}", (b'A' + t) as char);
+    }
+    return format!("{}", t);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use logic::testing::*;
+    use prng::*;
+
+    #[test]
+    fn hint_known_test() {
+        assert!(hint(&stack("AABBC")).is_none());
+
+        let h = hint(&stack("ABA")).unwrap();
+        assert_eq!(HintCategory::Joining, h.category);
+        assert_eq!(-1, h.fragmentation_delta);
+        assert_eq!(Some(1), h.moves_left);
+        assert!(h.explanation.contains("of A"));
+
+        let h = hint(&stack("ABCBD")).unwrap();
+        assert_eq!(HintCategory::Setup, h.category);
+        assert_eq!(0, h.fragmentation_delta);
+        assert_eq!(Some(2), h.moves_left);
+        assert!(h.explanation.contains("of B"));
+    }
+
+    #[test]
+    fn hint_follows_shortest_path_test() {
+        let ida = IdaSolver::new();
+        let mut seen_optimal_path = false;
+        for s in random_stacks(&mut PrngCtxSplitMix64::new(23), 100, 10, 5) {
+            let h = match hint(&s) { Some(h) => h, None => continue };
+            let next = after(&s, h.mv);
+            assert_eq!(next.fragmentation() - s.fragmentation(), h.fragmentation_delta);
+            if h.category != HintCategory::OptimalPath {
+                assert_eq!(h.category == HintCategory::Joining, h.fragmentation_delta < 0);
+            }
+            let total = h.moves_left.unwrap();
+            assert_eq!(ida.solve(&s).len(), total);
+            assert_eq!(total - 1, ida.solve(&next).len());
+            seen_optimal_path |= h.category == HintCategory::OptimalPath;
+        }
+        assert!(seen_optimal_path);
+    }
+
+    #[test]
+    fn hint_limits_test() {
+        // No search: the greedy move, with no length promised
+        let s = stack("ABCDEFGHABCDEFGH");
+        let h = hint_with_limits(&s, &Limits::none().with_max_nodes(0)).unwrap();
+        assert_eq!(None, h.moves_left);
+        assert_ne!(HintCategory::OptimalPath, h.category);
+        assert!(!h.explanation.contains("finished"));
+
+        // Just enough for the first search leaves nothing to check the greedy
+        // move with, so the searched move is used
+        let s = stack("ABCBD");
+        let optimal = IdaSolver::new().solve(&s);
+        let h = hint_with_limits(&s, &Limits::none().with_max_nodes(optimal.nodes_expanded))
+            .unwrap();
+        assert_eq!(Some(2), h.moves_left);
+        assert_eq!(optimal.moves[0], h.mv);
+    }
+}
+
+/* vim: set ts=4 sts=4 sw=4 et : */
@@ -0,0 +1,291 @@
+/*
+    Copyright (C) 2016  Erik Beran
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use logic::*;
+use solver::{Goal, Limits, Solution, Solver, Status};
+
+// Lower bound on the number of flips left before the stack is complete.
+//
+// Call a pair of neighbouring slices with different colors a break. A
+// complete stack has exactly one break fewer than it has colors, and any
+// other stack has more. A flip only reverses a run at one edge, so of all
+// the neighbouring pairs just the one at the inner end of the run changes;
+// a flip removes at most one break. That makes
+//     fragmentation() - distinct colors
+// a lower bound. When that is above zero and no flip removes a break (there
+// is no single joining move) the next flip can't make progress, so the
+// bound is one higher.
+pub fn lower_bound<const N : usize, T>(s : &SliceStackN<N, T>) -> i32 {
+    let h = s.fragmentation() - s.colors().len() as i32;
+    if h > 0 && s.find_single_joining_move().is_none() {
+        return h + 1;
+    }
+    return h;
+}
+
+// Number of breaks (see lower_bound)
+fn breaks(slices : &[SliceType]) -> i32 {
+    let mut b = 0;
+    for i in 1..slices.len() {
+        if slices[i - 1] != slices[i] {
+            b += 1;
+        }
+    }
+    return b;
+}
+
+// Breaks after `mv`, without applying it; only the pair at the inner end of
+// the flipped run changes.
+fn breaks_after(slices : &[SliceType], mv : Move, current : i32) -> i32 {
+    let count = slices.len();
+    let i = mv.index as usize;
+    let (outer, inner, neighbour) = match mv.side {
+        Side::Left => {
+            if i + 1 >= count { return current; }
+            (slices[0], slices[i], slices[i + 1])
+        }
+        Side::Right => {
+            if i == 0 { return current; }
+            (slices[count - 1], slices[i], slices[i - 1])
+        }
+    };
+    return current - (inner != neighbour) as i32 + (outer != neighbour) as i32;
+}
+
+// Iterative deepening A*: depth-first searches bounded by moves made plus
+// lower_bound, raising the bound to the smallest value that was cut off until
+// a solution turns up. Memory is just the current path, so it handles full
+// 16 slice, 8 color stacks that BfsSolver can't hold.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct IdaSolver;
+
+impl IdaSolver {
+    pub fn new() -> IdaSolver { IdaSolver }
+}
+
+impl<const N : usize> Solver<N> for IdaSolver {
+    fn solve_goal(&self, start : &SliceStackN<N>, goal : &Goal, limits : &Limits) -> Solution {
+        let start = start.without_objects();
+        if !goal.is_reachable(&start) {
+            return Solution::unsolved(0);
+        }
+        let goal = goal.resolve(&start);
+        let mut search = Search {
+            limits,
+            goal:&goal,
+            stopped:false,
+            goal_breaks:start.colors().len() as i32 - 1,
+            threshold:goal.lower_bound(&start),
+            next_threshold:i32::MAX,
+            nodes_expanded:0,
+            path:Vec::new(),
+            candidates:Vec::new(),
+        };
+        if goal.is_complete(&start) {
+            return Solution { moves:Vec::new(), status:Status::Optimal, nodes_expanded:0 };
+        }
+        let b = breaks(start.slices());
+        loop {
+            search.next_threshold = i32::MAX;
+            if search.dfs(&start, 0, b, None) {
+                return Solution { moves:search.path, status:Status::Optimal,
+                    nodes_expanded:search.nodes_expanded };
+            }
+            if search.stopped || search.next_threshold == i32::MAX {
+                return Solution::unsolved(search.nodes_expanded);
+            }
+            search.threshold = search.next_threshold;
+        }
+    }
+}
+
+struct Search<'a> {
+    limits : &'a Limits,
+    // Resolved, so Any or Exact
+    goal : &'a Goal,
+    // Set once the limits run out; the search then unwinds
+    stopped : bool,
+    goal_breaks : i32,
+    threshold : i32,
+    // Smallest f = g + h that exceeded the threshold this iteration
+    next_threshold : i32,
+    nodes_expanded : u64,
+    path : Vec<Move>,
+    // Per depth buffers of (h after move, breaks after move, move), reused
+    // across nodes. Breaks are only tracked for Goal::Any.
+    candidates : Vec<Vec<(i32, i32, Move)>>,
+}
+
+impl<'a> Search<'a> {
+    fn dfs<const N : usize>(&mut self, s : &SliceStackN<N>, g : i32, b : i32,
+                            prev : Option<Move>) -> bool {
+        if self.stopped || self.limits.exceeded(self.nodes_expanded) {
+            self.stopped = true;
+            return false;
+        }
+        self.nodes_expanded += 1;
+        let depth = g as usize;
+        if self.candidates.len() <= depth {
+            self.candidates.push(Vec::new());
+        }
+        let mut candidates = std::mem::take(&mut self.candidates[depth]);
+        candidates.clear();
+        let any = self.goal.is_any();
+        for mv in Move::all(s.count()) {
+            if skip(mv, prev, s.count(), any) {
+                continue;
+            }
+            if any {
+                let nb = breaks_after(s.slices(), mv, b);
+                candidates.push((nb - self.goal_breaks, nb, mv));
+            } else {
+                let mut next = *s;
+                mv.apply(&mut next);
+                candidates.push((self.goal.lower_bound(&next), 0, mv));
+            }
+        }
+        // Moves that bring the goal closer first
+        candidates.sort_by_key(|c| c.0);
+
+        let mut found = false;
+        for &(h, nb, mv) in candidates.iter() {
+            if !self.within_threshold(g + 1 + h) {
+                // Sorted, so the rest are no better
+                break;
+            }
+            let mut next = *s;
+            mv.apply(&mut next);
+            if h == 0 {
+                self.path.push(mv);
+                found = true;
+                break;
+            }
+            // Without a joining move the next flip can't remove a break
+            if any && next.find_single_joining_move().is_none()
+                && !self.within_threshold(g + 2 + h) {
+                continue;
+            }
+            self.path.push(mv);
+            if self.dfs(&next, g + 1, nb, Some(mv)) {
+                found = true;
+                break;
+            }
+            self.path.pop();
+            if self.stopped {
+                break;
+            }
+        }
+        self.candidates[depth] = candidates;
+        return found;
+    }
+
+    fn within_threshold(&mut self, f : i32) -> bool {
+        if f > self.threshold {
+            if f < self.next_threshold {
+                self.next_threshold = f;
+            }
+            return false;
+        }
+        return true;
+    }
+}
+
+// Moves that can't be part of a shortest solution we haven't already tried:
+// undoing the previous flip, reversing the whole stack when any color order
+// will do (it turns complete stacks into complete stacks, so the mirrored
+// remaining moves do the same job), and the second order of two flips on
+// separate runs (Left i and Right j with i < j commute; only Left first is
+// searched).
+fn skip(mv : Move, prev : Option<Move>, count : i32, any : bool) -> bool {
+    if any && mv.side == Side::Left && mv.index == count - 1 {
+        return true;
+    }
+    return match prev {
+        Some(p) if p == mv => true,
+        Some(p) => p.side == Side::Right && mv.side == Side::Left && mv.index < p.index,
+        None => false,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use logic::testing::*;
+    use prng::*;
+    use solver::BfsSolver;
+
+    #[test]
+    fn lower_bound_test() {
+        let s : SliceStack = "AABBC".parse().unwrap();
+        assert_eq!(0, lower_bound(&s));
+        // One break too many and a joining move exists
+        let s : SliceStack = "ABA".parse().unwrap();
+        assert_eq!(1, lower_bound(&s));
+        // One break too many but no joining move
+        let s : SliceStack = "ABCBD".parse().unwrap();
+        assert_eq!(2, lower_bound(&s));
+    }
+
+    #[test]
+    fn breaks_after_test() {
+        let s : SliceStack = "ABCAB".parse().unwrap();
+        let b = breaks(s.slices());
+        for mv in Move::all(s.count()) {
+            let mut next = s;
+            mv.apply(&mut next);
+            assert_eq!(breaks(next.slices()), breaks_after(s.slices(), mv, b));
+        }
+    }
+
+    #[test]
+    fn ida_matches_bfs_test() {
+        let bfs = BfsSolver::new();
+        let ida = IdaSolver::new();
+        for s in random_stacks(&mut PrngCtxSplitMix64::new(12), 60, 9, 4) {
+            let expected = bfs.solve(&s);
+            let solution = ida.solve(&s);
+            assert!(solution.is_optimal());
+            assert_eq!(expected.len(), solution.len());
+            assert!(solution.apply(&s).iscomplete());
+            assert!(lower_bound(&s) <= solution.len() as i32);
+        }
+    }
+
+    #[test]
+    fn ida_full_stack_test() {
+        let ida = IdaSolver::new();
+        for s in random_stacks(&mut PrngCtxGaloisLsfw::new(34), 4, MAX_SLICES as i32, 8) {
+            let solution = ida.solve(&s);
+            assert!(solution.is_optimal());
+            assert!(solution.apply(&s).iscomplete());
+            assert!(lower_bound(&s) <= solution.len() as i32);
+        }
+    }
+
+    #[test]
+    fn ida_limits_test() {
+        let s : SliceStack = "ABCDEFGHABCDEFGH".parse().unwrap();
+        let full = IdaSolver::new().solve(&s);
+        let max_nodes = full.nodes_expanded - 1;
+        let limits = Limits::none().with_max_nodes(max_nodes);
+        let solution = IdaSolver::new().solve_with_limits(&s, &limits);
+        assert!(!solution.is_solved());
+        assert_eq!(max_nodes, solution.nodes_expanded);
+    }
+}
+
+/* vim: set ts=4 sts=4 sw=4 et : */
@@ -0,0 +1,134 @@
+/*
+    Copyright (C) 2016  Erik Beran
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Reading the clock costs more than expanding a node, so the deadline is
+// only checked this often
+const CLOCK_INTERVAL : u64 = 256;
+
+// Shared flag for stopping a search from another thread; clones share it
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled : Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new() -> CancelToken { CancelToken::default() }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        return self.cancelled.load(Ordering::Relaxed);
+    }
+}
+
+// Budget for Solver::solve_with_limits. A search that runs out stops where
+// it is and returns what it has, which is often Status::Unsolved. The
+// default has no limits.
+#[derive(Debug, Clone, Default)]
+pub struct Limits {
+    pub max_nodes : Option<u64>,
+    pub deadline : Option<Instant>,
+    pub cancel : Option<CancelToken>,
+}
+
+impl Limits {
+    pub fn none() -> Limits { Limits::default() }
+
+    pub fn with_max_nodes(mut self, max_nodes : u64) -> Limits {
+        self.max_nodes = Some(max_nodes);
+        return self;
+    }
+
+    pub fn with_deadline(mut self, deadline : Instant) -> Limits {
+        self.deadline = Some(deadline);
+        return self;
+    }
+
+    // Deadline `timeout` from now
+    pub fn with_timeout(self, timeout : Duration) -> Limits {
+        return self.with_deadline(Instant::now() + timeout);
+    }
+
+    pub fn with_cancel(mut self, cancel : CancelToken) -> Limits {
+        self.cancel = Some(cancel);
+        return self;
+    }
+
+    // What is left for another search once `nodes` nodes have been expanded;
+    // the deadline and cancel token are shared
+    pub fn remaining(&self, nodes : u64) -> Limits {
+        let mut limits = self.clone();
+        limits.max_nodes = self.max_nodes.map(|max_nodes| max_nodes.saturating_sub(nodes));
+        return limits;
+    }
+
+    // Whether a search that has expanded `nodes` nodes has to stop
+    pub fn exceeded(&self, nodes : u64) -> bool {
+        if let Some(max_nodes) = self.max_nodes {
+            if nodes >= max_nodes {
+                return true;
+            }
+        }
+        if let Some(ref cancel) = self.cancel {
+            if cancel.is_cancelled() {
+                return true;
+            }
+        }
+        if let Some(deadline) = self.deadline {
+            if nodes.is_multiple_of(CLOCK_INTERVAL) && Instant::now() >= deadline {
+                return true;
+            }
+        }
+        return false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_test() {
+        assert!(!Limits::none().exceeded(u64::MAX - 1));
+        let limits = Limits::none().with_max_nodes(10);
+        assert!(!limits.exceeded(9));
+        assert!(limits.exceeded(10));
+        assert!(limits.remaining(4).exceeded(6));
+        assert!(limits.remaining(20).exceeded(0));
+        assert!(!Limits::none().remaining(20).exceeded(0));
+
+        let cancel = CancelToken::new();
+        let limits = Limits::none().with_cancel(cancel.clone());
+        assert!(!limits.exceeded(1));
+        cancel.cancel();
+        assert!(limits.exceeded(1));
+
+        let past = Limits::none().with_deadline(Instant::now());
+        assert!(past.exceeded(0));
+        // Between clock checks
+        assert!(!past.exceeded(1));
+        assert!(!Limits::none().with_timeout(Duration::from_secs(60)).exceeded(0));
+    }
+}
+
+/* vim: set ts=4 sts=4 sw=4 et : */
@@ -0,0 +1,306 @@
+/*
+    Copyright (C) 2016  Erik Beran
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use logic::*;
+use rayon::prelude::*;
+use solver::goal::placement_moves;
+use solver::{Goal, GreedySolver, Limits, Policy, SeededRandom, Solution, Solver, Status};
+
+pub const DEFAULT_MCTS_ITERATIONS : usize = 2000;
+// Rewards are lower_bound / solution length, so they sit close together;
+// the usual sqrt(2) would make the search close to uniform
+pub const DEFAULT_EXPLORATION : f64 = 0.1;
+
+// Monte Carlo tree search over flips. Each iteration walks down the tree by
+// UCT, expands a leaf with every flip and plays a greedy rollout (random
+// edge, see SeededRandom) from each new child, the rollouts running in
+// parallel. The shortest complete line seen is kept, starting with plain
+// greedy, so the result is never longer than GreedySolver's.
+//
+// Subtrees that can't beat the best line (moves so far + lower bound) are
+// pruned; if that empties the whole tree the best line is proven optimal.
+// Every rollout is seeded from `seed` and its node, so a given seed gives
+// the same moves however the threads are scheduled.
+#[derive(Debug, Copy, Clone)]
+pub struct MctsSolver {
+    // Leaves expanded
+    pub iterations : usize,
+    pub exploration : f64,
+    pub seed : u64,
+}
+
+impl Default for MctsSolver {
+    fn default() -> MctsSolver { MctsSolver::new(0) }
+}
+
+impl MctsSolver {
+    pub fn new(seed : u64) -> MctsSolver {
+        MctsSolver { iterations:DEFAULT_MCTS_ITERATIONS, exploration:DEFAULT_EXPLORATION, seed }
+    }
+}
+
+struct Node<const N : usize> {
+    stack : SliceStackN<N>,
+    parent : usize,
+    // Move from the parent; never read for the root
+    mv : Move,
+    depth : usize,
+    lower_bound : i32,
+    children : Vec<usize>,
+    expanded : bool,
+    // Nothing below can beat the best line
+    dead : bool,
+    visits : u64,
+    reward : f64,
+}
+
+// Moves from the root to `index`
+fn path_to<const N : usize>(nodes : &[Node<N>], mut index : usize) -> Vec<Move> {
+    let mut moves = Vec::new();
+    while index != 0 {
+        moves.push(nodes[index].mv);
+        index = nodes[index].parent;
+    }
+    moves.reverse();
+    return moves;
+}
+
+// Moves to finish `s`: greedy for Goal::Any, otherwise placing slices
+fn rollout<const N : usize>(s : &SliceStackN<N>, goal : &Goal, seed : u64) -> Vec<Move> {
+    if let Goal::Exact(ref target) = *goal {
+        return placement_moves(s, target);
+    }
+    let mut policy = SeededRandom::new(seed);
+    let mut s = *s;
+    let mut moves = Vec::new();
+    while !s.iscomplete() {
+        let mv = policy.next_move(&s);
+        mv.apply(&mut s);
+        moves.push(mv);
+    }
+    return moves;
+}
+
+impl MctsSolver {
+    // Child of `index` with the best UCT score among the ones that could
+    // still beat a line of `best_len` moves
+    fn select<const N : usize>(&self, nodes : &[Node<N>], index : usize, best_len : usize)
+        -> Option<usize> {
+        let ln_visits = (nodes[index].visits.max(1) as f64).ln();
+        let mut best = None;
+        let mut best_score = f64::NEG_INFINITY;
+        for &c in nodes[index].children.iter() {
+            let child = &nodes[c];
+            if child.dead || child.depth + child.lower_bound as usize >= best_len {
+                continue;
+            }
+            let score = if child.visits == 0 {
+                f64::INFINITY
+            } else {
+                child.reward / child.visits as f64
+                    + self.exploration * (ln_visits / child.visits as f64).sqrt()
+            };
+            if score > best_score {
+                best_score = score;
+                best = Some(c);
+            }
+        }
+        return best;
+    }
+}
+
+impl<const N : usize> Solver<N> for MctsSolver {
+    fn solve_goal(&self, start : &SliceStackN<N>, goal : &Goal, limits : &Limits) -> Solution {
+        let start = start.without_objects();
+        if !goal.is_reachable(&start) {
+            return Solution::unsolved(0);
+        }
+        let goal = goal.resolve(&start);
+        if goal.is_complete(&start) {
+            return Solution { moves:Vec::new(), status:Status::Optimal, nodes_expanded:0 };
+        }
+
+        let root_bound = goal.lower_bound(&start);
+        // Plain greedy first, so there is always an answer and it is never
+        // longer than GreedySolver's
+        let mut best = GreedySolver::default().solve_goal(&start, &goal, &Limits::none()).moves;
+        let mut nodes = vec![Node { stack:start, parent:0, mv:Move::new(0, Side::Left), depth:0,
+            lower_bound:root_bound, children:Vec::new(), expanded:false, dead:false, visits:0,
+            reward:0.0 }];
+        let mut nodes_expanded : u64 = 0;
+
+        while (nodes_expanded as usize) < self.iterations && !nodes[0].dead
+            && best.len() as i32 > root_bound {
+            if limits.exceeded(nodes_expanded) {
+                break;
+            }
+            // Down to a leaf. A node with nothing left worth trying dies, and
+            // the next iteration starts over.
+            let mut index = 0;
+            while nodes[index].expanded {
+                match self.select(&nodes, index, best.len()) {
+                    Some(c) => index = c,
+                    None => {
+                        nodes[index].dead = true;
+                        break;
+                    }
+                }
+            }
+            if nodes[index].dead {
+                continue;
+            }
+
+            nodes_expanded += 1;
+            nodes[index].expanded = true;
+            let depth = nodes[index].depth + 1;
+            let s = nodes[index].stack;
+            let undo = if index == 0 { None } else { Some(nodes[index].mv) };
+            for mv in Move::all(s.count()) {
+                if Some(mv) == undo {
+                    continue;
+                }
+                let mut next = s;
+                mv.apply(&mut next);
+                let lower_bound = goal.lower_bound(&next);
+                let dead = depth + lower_bound as usize >= best.len();
+                let child = nodes.len();
+                nodes.push(Node { stack:next, parent:index, mv, depth, lower_bound,
+                    children:Vec::new(), expanded:false, dead, visits:0, reward:0.0 });
+                nodes[index].children.push(child);
+            }
+
+            let jobs : Vec<(usize, SliceStackN<N>, u64)> = nodes[index].children.iter()
+                .filter(|c| !nodes[**c].dead)
+                .map(|c| (*c, nodes[*c].stack, self.seed ^ (*c as u64).wrapping_mul(SEED_STEP)))
+                .collect();
+            let mut results = Vec::new();
+            jobs.par_iter()
+                .map(|&(c, ref s, seed)| (c, rollout(s, &goal, seed)))
+                .collect_into(&mut results);
+
+            for (c, moves) in results.into_iter() {
+                let len = nodes[c].depth + moves.len();
+                if len < best.len() {
+                    best = path_to(&nodes, c);
+                    best.extend(moves);
+                }
+                let reward = root_bound as f64 / len as f64;
+                let mut i = c;
+                loop {
+                    nodes[i].visits += 1;
+                    nodes[i].reward += reward;
+                    if i == 0 {
+                        break;
+                    }
+                    i = nodes[i].parent;
+                }
+            }
+        }
+
+        // Nodes die only when nothing below them can beat the best line, and
+        // that only gets shorter, so a dead root proves it
+        let status = if nodes[0].dead || best.len() as i32 == root_bound {
+            Status::Optimal
+        } else {
+            Status::Solved
+        };
+        return Solution { moves:best, status, nodes_expanded };
+    }
+}
+
+// Spreads the per node rollout seeds (the 64 bit golden ratio, as SplitMix64
+// uses)
+const SEED_STEP : u64 = 0x9e37_79b9_7f4a_7c15;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use logic::testing::*;
+    use prng::*;
+    use solver::IdaSolver;
+
+    #[test]
+    fn mcts_known_test() {
+        let mcts = MctsSolver::default();
+        let done : SliceStack = "AABBC".parse().unwrap();
+        assert!(mcts.solve(&done).is_optimal());
+
+        let s : SliceStack = "ABCBD".parse().unwrap();
+        let solution = mcts.solve(&s);
+        assert_eq!(2, solution.len());
+        assert!(solution.apply(&s).iscomplete());
+    }
+
+    #[test]
+    fn mcts_vs_greedy_test() {
+        let mcts = MctsSolver { iterations:200, ..MctsSolver::new(5) };
+        let greedy = GreedySolver::default();
+        let ida = IdaSolver::new();
+        let (mut mcts_total, mut greedy_total) = (0, 0);
+        for s in random_stacks(&mut PrngCtxGaloisLsfw::new(34), 10, 12, 6) {
+            let solution = mcts.solve(&s);
+            assert!(solution.apply(&s).iscomplete());
+            let greedy_len = greedy.solve(&s).len();
+            assert!(solution.len() <= greedy_len);
+            let optimal = ida.solve(&s).len();
+            assert!(solution.len() >= optimal);
+            if solution.is_optimal() {
+                assert_eq!(optimal, solution.len());
+            }
+            // Same seed, same moves
+            assert_eq!(solution, mcts.solve(&s));
+            mcts_total += solution.len();
+            greedy_total += greedy_len;
+        }
+        assert!(mcts_total < greedy_total);
+    }
+
+    #[test]
+    fn mcts_never_worse_than_greedy_test() {
+        // Even with no search at all
+        let greedy = GreedySolver::default();
+        let one = MctsSolver { iterations:1, ..MctsSolver::new(9) };
+        let no_nodes = Limits::none().with_max_nodes(0);
+        for s in random_stacks(&mut PrngCtxGaloisLsfw::new(34), 100, MAX_SLICES as i32, 8) {
+            let greedy_len = greedy.solve(&s).len();
+            let unsearched = MctsSolver::new(9).solve_with_limits(&s, &no_nodes);
+            assert!(unsearched.apply(&s).iscomplete());
+            assert!(unsearched.len() <= greedy_len);
+            assert!(one.solve(&s).len() <= greedy_len);
+        }
+    }
+
+    #[test]
+    fn mcts_large_stack_test() {
+        // Past MAX_SLICES, and toward a color order
+        let mut rng = PrngCtxSplitMix64::new(25);
+        let mut s = SliceStackN::<24>::new();
+        s.init_with(&mut rng, 24, 8);
+        let mcts = MctsSolver { iterations:50, ..MctsSolver::new(1) };
+        let solution = mcts.solve(&s);
+        assert!(solution.apply(&s).iscomplete());
+        assert!(solution.len() <= GreedySolver::default().solve(&s).len());
+
+        let goal = Goal::sorted(8);
+        let solution = mcts.solve_goal(&s, &goal, &Limits::none());
+        assert!(goal.is_complete(&solution.apply(&s)));
+        assert_eq!(1, MctsSolver::new(1).solve_goal(&s, &goal, &Limits::none().with_max_nodes(1))
+                       .nodes_expanded);
+    }
+}
+
+/* vim: set ts=4 sts=4 sw=4 et : */
@@ -0,0 +1,112 @@
+/*
+    Copyright (C) 2016  Erik Beran
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use logic::*;
+
+mod anytime;
+mod beam;
+mod bfs;
+mod goal;
+mod greedy;
+mod hint;
+mod ida;
+mod limits;
+mod mcts;
+mod optimize;
+mod policy;
+mod solutions;
+mod table;
+mod verify;
+
+pub use self::anytime::Anytime;
+pub use self::beam::{BeamSolver, Evaluation, Fragmentation, LowerBound};
+pub use self::bfs::BfsSolver;
+pub use self::goal::Goal;
+pub use self::greedy::{GreedySolver, RunSafety};
+pub use self::hint::{hint, hint_with_limits, Hint, HintCategory};
+pub use self::ida::{lower_bound, IdaSolver};
+pub use self::limits::{CancelToken, Limits};
+pub use self::mcts::MctsSolver;
+pub use self::optimize::Optimizer;
+pub use self::policy::{average_moves, Alternating, LeftFirst, Lookahead, Policy, PolicyGreedy,
+                       RightFirst, SeededRandom};
+pub use self::solutions::{Equivalence, OptimalSolutions, Solutions};
+pub use self::table::{DistanceTable, TableSolver};
+pub use self::verify::{verify, Replay, ReplayError, VerifiedSolution, VerifyError};
+
+// Anything that can take a stack to a complete one. Solvers never modify the
+// stack passed in; the moves come back in the Solution.
+pub trait Solver<const N : usize = MAX_SLICES> {
+    // Moves from `s` to a stack that meets `goal`, stopping early once
+    // `limits` runs out. Solvers that don't search ignore the limits. A goal
+    // the stack can't reach (see Goal::is_reachable) gives Status::Unsolved.
+    fn solve_goal(&self, s : &SliceStackN<N>, goal : &Goal, limits : &Limits) -> Solution;
+
+    fn solve(&self, s : &SliceStackN<N>) -> Solution {
+        return self.solve_goal(s, &Goal::Any, &Limits::none());
+    }
+
+    fn solve_with_limits(&self, s : &SliceStackN<N>, limits : &Limits) -> Solution {
+        return self.solve_goal(s, &Goal::Any, limits);
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Status {
+    // Moves complete the stack and no shorter sequence exists
+    Optimal,
+    // Moves complete the stack, but may not be the shortest way
+    Solved,
+    // The search stopped (e.g. at a memory or node limit) before finding a
+    // solution
+    Unsolved,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Solution {
+    // Empty when unsolved
+    pub moves : Vec<Move>,
+    pub status : Status,
+    // Search effort; states whose successors were generated
+    pub nodes_expanded : u64,
+}
+
+impl Solution {
+    pub fn unsolved(nodes_expanded : u64) -> Solution {
+        Solution { moves:Vec::new(), status:Status::Unsolved, nodes_expanded }
+    }
+
+    // Number of moves
+    pub fn len(&self) -> usize { self.moves.len() }
+
+    pub fn is_empty(&self) -> bool { self.moves.is_empty() }
+
+    pub fn is_solved(&self) -> bool { self.status != Status::Unsolved }
+
+    pub fn is_optimal(&self) -> bool { self.status == Status::Optimal }
+
+    // Applies the moves to a copy of `start`
+    pub fn apply<const N : usize>(&self, start : &SliceStackN<N>) -> SliceStackN<N> {
+        let mut s = *start;
+        for mv in self.moves.iter() {
+            mv.apply(&mut s);
+        }
+        return s;
+    }
+}
+
+/* vim: set ts=4 sts=4 sw=4 et : */
@@ -0,0 +1,310 @@
+/*
+    Copyright (C) 2016  Erik Beran
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use logic::*;
+use solver::{verify, BfsSolver, Solver, VerifiedSolution, VerifyError};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+pub const DEFAULT_WINDOW : usize = 6;
+pub const DEFAULT_MAX_STATES : usize = 64 * 1024;
+
+// Shortens a solution after the fact. Rules, repeated until none applies:
+//  - cut out any stretch that comes back to a stack seen before (this also
+//    drops no-op flips and a flip that is immediately undone)
+//  - replace two flips by one when a single flip gets to the same stack
+//  - replace each `window` moves by a shortest path between the same two
+//    stacks, found by bidirectional search
+//  - re-solve the last `window` moves optimally; any complete stack will do
+// The searches give up past max_states, leaving that stretch as it was.
+#[derive(Debug, Copy, Clone)]
+pub struct Optimizer {
+    pub window : usize,
+    pub max_states : usize,
+}
+
+impl Default for Optimizer {
+    fn default() -> Optimizer { Optimizer::new() }
+}
+
+impl Optimizer {
+    pub fn new() -> Optimizer {
+        Optimizer { window:DEFAULT_WINDOW, max_states:DEFAULT_MAX_STATES }
+    }
+
+    // `moves` must solve `start` (see verify), except that no-op flips are
+    // allowed since cut_cycles drops them; the result is checked by verify
+    // and is never longer
+    pub fn optimize<const N : usize>(&self, start : &SliceStackN<N>, moves : &[Move])
+        -> Result<VerifiedSolution<N>, VerifyError<N>> {
+        let legal = moves.iter().all(|mv| mv.is_legal(start.count()));
+        if !legal || !trajectory(start, moves).last().unwrap().iscomplete() {
+            // Not a solution at all; verify says why
+            return Err(verify(start, moves).unwrap_err());
+        }
+        let mut moves = moves.to_vec();
+        loop {
+            let len = moves.len();
+            moves = cut_cycles(start, &moves);
+            moves = merge_pairs(start, &moves);
+            moves = self.resolve_windows(start, &moves);
+            moves = self.resolve_tail(start, &moves);
+            if moves.len() == len {
+                break;
+            }
+        }
+        return verify(start, &moves);
+    }
+
+    fn resolve_windows<const N : usize>(&self, start : &SliceStackN<N>, moves : &[Move])
+        -> Vec<Move> {
+        let mut moves = moves.to_vec();
+        let w = self.window.min(moves.len());
+        let mut i = 0;
+        while w >= 2 && i + w <= moves.len() {
+            let states = trajectory(start, &moves);
+            if let Some(path) = shortest_path(&states[i], &states[i + w], w - 1,
+                                              self.max_states) {
+                moves.splice(i..i + w, path);
+            }
+            i += 1;
+        }
+        return moves;
+    }
+
+    fn resolve_tail<const N : usize>(&self, start : &SliceStackN<N>, moves : &[Move])
+        -> Vec<Move> {
+        let w = self.window.min(moves.len());
+        let states = trajectory(start, moves);
+        let from = moves.len() - w;
+        let tail = BfsSolver { max_states:self.max_states }.solve(&states[from]);
+        if !tail.is_solved() || tail.len() >= w {
+            return moves.to_vec();
+        }
+        let mut result = moves[0..from].to_vec();
+        result.extend(tail.moves);
+        return result;
+    }
+}
+
+// The stack before each move, then the final stack
+fn trajectory<const N : usize>(start : &SliceStackN<N>, moves : &[Move]) -> Vec<SliceStackN<N>> {
+    let mut states = Vec::with_capacity(moves.len() + 1);
+    let mut s = *start;
+    states.push(s);
+    for mv in moves.iter() {
+        mv.apply(&mut s);
+        states.push(s);
+    }
+    return states;
+}
+
+fn cut_cycles<const N : usize>(start : &SliceStackN<N>, moves : &[Move]) -> Vec<Move> {
+    let states = trajectory(start, moves);
+    // Last time each stack appears
+    let mut last : HashMap<SliceStackN<N>, usize> = HashMap::new();
+    for (i, s) in states.iter().enumerate() {
+        last.insert(*s, i);
+    }
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < moves.len() {
+        // Jump past everything until the stack is last seen
+        i = last[&states[i]];
+        if i < moves.len() {
+            result.push(moves[i]);
+            i += 1;
+        }
+    }
+    return result;
+}
+
+fn merge_pairs<const N : usize>(start : &SliceStackN<N>, moves : &[Move]) -> Vec<Move> {
+    let states = trajectory(start, moves);
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < moves.len() {
+        if i + 1 < moves.len() {
+            let single = Move::all(states[i].count()).find(|mv| {
+                let mut s = states[i];
+                mv.apply(&mut s);
+                return s == states[i + 2];
+            });
+            if let Some(mv) = single {
+                result.push(mv);
+                i += 2;
+                continue;
+            }
+        }
+        result.push(moves[i]);
+        i += 1;
+    }
+    return result;
+}
+
+// state -> (neighbour one step nearer the search's own start, move between)
+type Links<const N : usize> = HashMap<SliceStackN<N>, Option<(SliceStackN<N>, Move)>>;
+
+// Shortest flips from `from` to `to` if there is a path of at most max_len;
+// searches from both ends, a layer at a time from the smaller side
+fn shortest_path<const N : usize>(from : &SliceStackN<N>, to : &SliceStackN<N>, max_len : usize,
+                                  max_states : usize) -> Option<Vec<Move>> {
+    if from == to {
+        return Some(Vec::new());
+    }
+    let mut forward : Links<N> = HashMap::new();
+    let mut backward : Links<N> = HashMap::new();
+    forward.insert(*from, None);
+    backward.insert(*to, None);
+    let mut forward_layer = vec![*from];
+    let mut backward_layer = vec![*to];
+    let mut depth = 0;
+
+    while depth < max_len {
+        depth += 1;
+        let expand_forward = forward_layer.len() <= backward_layer.len();
+        let (links, other, layer) = if expand_forward {
+            (&mut forward, &backward, &mut forward_layer)
+        } else {
+            (&mut backward, &forward, &mut backward_layer)
+        };
+        let mut next_layer = Vec::new();
+        let mut meet : Option<SliceStackN<N>> = None;
+        for s in layer.iter() {
+            for mv in Move::all(s.count()) {
+                let mut next = *s;
+                mv.apply(&mut next);
+                if let Entry::Vacant(e) = links.entry(next) {
+                    e.insert(Some((*s, mv)));
+                    if other.contains_key(&next) {
+                        meet = Some(next);
+                        break;
+                    }
+                    next_layer.push(next);
+                }
+            }
+            if meet.is_some() {
+                break;
+            }
+        }
+        if let Some(m) = meet {
+            // Layers grow one at a time, so the first meeting is shortest
+            let mut path = walk(&forward, m);
+            path.reverse();
+            path.extend(walk(&backward, m));
+            return Some(path);
+        }
+        if next_layer.is_empty() || forward.len() + backward.len() > max_states {
+            return None;
+        }
+        *layer = next_layer;
+    }
+    return None;
+}
+
+// Moves from `s` back to the start of the search that produced `links`
+fn walk<const N : usize>(links : &Links<N>, mut s : SliceStackN<N>) -> Vec<Move> {
+    let mut moves = Vec::new();
+    while let Some(&Some((prev, mv))) = links.get(&s) {
+        moves.push(mv);
+        s = prev;
+    }
+    return moves;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use logic::testing::*;
+    use prng::*;
+    use solver::{GreedySolver, IdaSolver};
+
+    #[test]
+    fn peephole_test() {
+        let s = stack("ABCBD");
+        let l1 = Move::new(1, Side::Left);
+        let l2 = Move::new(2, Side::Left);
+        let r3 = Move::new(3, Side::Right);
+        // R3 undone right away, then the solution
+        assert_eq!(vec![l1, l2], cut_cycles(&s, &[r3, r3, l1, l2]));
+        // Flipping the whole stack twice comes back too
+        let whole = Move::new(4, Side::Left);
+        assert_eq!(vec![l1, l2], cut_cycles(&s, &[l1, whole, whole, l2]));
+
+        // ABCAB -> BACBA -> ABCBA, which is just R3
+        let t = stack("ABCAB");
+        let pair = [Move::new(4, Side::Left), Move::new(1, Side::Left)];
+        assert_eq!(vec![r3], merge_pairs(&t, &pair));
+        assert_eq!(vec![l1, l2], merge_pairs(&s, &[l1, l2]));
+    }
+
+    #[test]
+    fn shortest_path_test() {
+        let a = stack("ABCDEF");
+        let mut b = a;
+        let moves = [Move::new(3, Side::Left), Move::new(2, Side::Right), Move::new(1, Side::Left)];
+        for mv in moves.iter() {
+            mv.apply(&mut b);
+        }
+        let path = shortest_path(&a, &b, 3, DEFAULT_MAX_STATES).unwrap();
+        assert!(path.len() <= 3);
+        assert_eq!(b, *trajectory(&a, &path).last().unwrap());
+        assert_eq!(None, shortest_path(&a, &b, path.len() - 1, DEFAULT_MAX_STATES));
+        assert_eq!(Some(Vec::new()), shortest_path(&a, &a, 0, DEFAULT_MAX_STATES));
+    }
+
+    #[test]
+    fn optimize_greedy_test() {
+        let greedy = GreedySolver::default();
+        let ida = IdaSolver::new();
+        let optimizer = Optimizer::new();
+        let (mut before, mut after) = (0, 0);
+        for s in random_stacks(&mut PrngCtxGaloisLsfw::new(34), 8, MAX_SLICES as i32, 8) {
+            let solution = greedy.solve(&s);
+            let optimized = optimizer.optimize(&s, &solution.moves).unwrap();
+            assert!(optimized.len() <= solution.len());
+            assert!(optimized.len() >= ida.solve(&s).len());
+            before += solution.len();
+            after += optimized.len();
+        }
+        assert!(after < before, "optimized {} greedy {}", after, before);
+
+        // Past MAX_SLICES
+        let mut s = SliceStackN::<24>::new();
+        s.init_with(&mut PrngCtxSplitMix64::new(20), 24, 8);
+        let solution = greedy.solve(&s);
+        let optimized = optimizer.optimize(&s, &solution.moves).unwrap();
+        assert!(optimized.len() <= solution.len());
+        assert!(optimized.end.iscomplete());
+
+        // Invalid input is reported, not optimized
+        let s = stack("ABCBD");
+        assert_eq!(Some(0), optimizer.optimize(&s, &[Move::new(1, Side::Left)])
+                   .unwrap_err().step());
+        assert_eq!(Some(1), optimizer.optimize(&s, &[Move::new(1, Side::Left),
+                                                     Move::new(7, Side::Left)])
+                   .unwrap_err().step());
+
+        // No-op flips are dropped rather than rejected
+        let l1 = Move::new(1, Side::Left);
+        let l2 = Move::new(2, Side::Left);
+        let optimized = optimizer.optimize(&s, &[l1, Move::new(0, Side::Left), l2]).unwrap();
+        assert_eq!(vec![l1, l2], optimized.moves);
+    }
+}
+
+/* vim: set ts=4 sts=4 sw=4 et : */
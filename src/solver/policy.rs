@@ -0,0 +1,257 @@
+/*
+    Copyright (C) 2016  Erik Beran
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use logic::*;
+use prng::*;
+use solver::goal::placement_moves;
+use solver::{Goal, Limits, Solution, Solver, Status};
+
+// Picks the greedy solver's next move. Each solve starts from a fresh clone
+// of the policy, so state such as a random generator doesn't carry over
+// between stacks.
+pub trait Policy : Clone {
+    // Called only for incomplete stacks
+    fn next_move<const N : usize>(&mut self, s : &SliceStackN<N>) -> Move;
+}
+
+// A joining move on `first`'s edge, then the other edge, otherwise a setup
+// move searching from `first`'s end
+fn greedy_move<const N : usize>(s : &SliceStackN<N>, first : Side) -> Move {
+    let search_dir = match first { Side::Left => 0, Side::Right => 1 };
+    return s.find_single_joining_move_on(first)
+        .or_else(|| s.find_single_joining_move_on(first.opposite()))
+        .or_else(|| s.find_first_double_move(search_dir))
+        // An incomplete stack always has a double move
+        .unwrap();
+}
+
+// What GreedySolver does: left edge first, search_dir = 0
+#[derive(Debug, Copy, Clone, Default)]
+pub struct LeftFirst;
+
+impl Policy for LeftFirst {
+    fn next_move<const N : usize>(&mut self, s : &SliceStackN<N>) -> Move {
+        return greedy_move(s, Side::Left);
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+pub struct RightFirst;
+
+impl Policy for RightFirst {
+    fn next_move<const N : usize>(&mut self, s : &SliceStackN<N>) -> Move {
+        return greedy_move(s, Side::Right);
+    }
+}
+
+// Switches the preferred edge after every move (search_dir = !search_dir)
+#[derive(Debug, Copy, Clone)]
+pub struct Alternating {
+    next : Side,
+}
+
+impl Default for Alternating {
+    fn default() -> Alternating { Alternating::new() }
+}
+
+impl Alternating {
+    pub fn new() -> Alternating { Alternating { next:Side::Left } }
+}
+
+impl Policy for Alternating {
+    fn next_move<const N : usize>(&mut self, s : &SliceStackN<N>) -> Move {
+        let mv = greedy_move(s, self.next);
+        self.next = self.next.opposite();
+        return mv;
+    }
+}
+
+// Prefers a random edge each move; the same seed always gives the same moves
+#[derive(Debug, Copy, Clone)]
+pub struct SeededRandom {
+    rng : PrngCtxSplitMix64,
+}
+
+impl SeededRandom {
+    pub fn new(seed : u64) -> SeededRandom { SeededRandom { rng:PrngCtxSplitMix64::new(seed) } }
+}
+
+impl Policy for SeededRandom {
+    fn next_move<const N : usize>(&mut self, s : &SliceStackN<N>) -> Move {
+        let first = if self.rng.next_bounded(2) == 0 { Side::Left } else { Side::Right };
+        return greedy_move(s, first);
+    }
+}
+
+// Tries every sequence of up to `depth` flips, finishing each with the
+// LeftFirst greedy, and takes the first move of the shortest total. The
+// plain greedy line is one of the candidates, so the estimate drops by at
+// least one per move and the result is never longer than LeftFirst's.
+// Costs about (2 * count)^depth greedy runs per move.
+#[derive(Debug, Copy, Clone)]
+pub struct Lookahead {
+    pub depth : u32,
+}
+
+impl Lookahead {
+    pub fn new(depth : u32) -> Lookahead { Lookahead { depth } }
+
+    // Fewest moves to complete `s` with up to `depth` free flips followed by
+    // greedy
+    fn estimate<const N : usize>(&self, s : &SliceStackN<N>, depth : u32) -> usize {
+        let mut best = greedy_len(s);
+        if depth == 0 || best <= 1 {
+            return best;
+        }
+        for mv in Move::all(s.count()) {
+            let mut next = *s;
+            mv.apply(&mut next);
+            let len = 1 + self.estimate(&next, depth - 1);
+            if len < best {
+                best = len;
+            }
+        }
+        return best;
+    }
+}
+
+fn greedy_len<const N : usize>(s : &SliceStackN<N>) -> usize {
+    let mut s = *s;
+    let mut len = 0;
+    while !s.iscomplete() {
+        greedy_move(&s, Side::Left).apply(&mut s);
+        len += 1;
+    }
+    return len;
+}
+
+impl Policy for Lookahead {
+    fn next_move<const N : usize>(&mut self, s : &SliceStackN<N>) -> Move {
+        let s = s.without_objects();
+        // Ties go to the greedy move
+        let mut best_move = greedy_move(&s, Side::Left);
+        let mut next = s;
+        best_move.apply(&mut next);
+        let mut best = self.estimate(&next, self.depth.saturating_sub(1));
+        for mv in Move::all(s.count()) {
+            let mut next = s;
+            mv.apply(&mut next);
+            let len = self.estimate(&next, self.depth.saturating_sub(1));
+            if len < best {
+                best = len;
+                best_move = mv;
+            }
+        }
+        return best_move;
+    }
+}
+
+// The greedy loop with the move choice left to `policy`. Other goals than
+// Goal::Any are put together like GreedySolver does.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct PolicyGreedy<P> {
+    pub policy : P,
+}
+
+impl<P : Policy> PolicyGreedy<P> {
+    pub fn new(policy : P) -> PolicyGreedy<P> { PolicyGreedy { policy } }
+}
+
+impl<const N : usize, P : Policy> Solver<N> for PolicyGreedy<P> {
+    fn solve_goal(&self, start : &SliceStackN<N>, goal : &Goal, _limits : &Limits) -> Solution {
+        let mut policy = self.policy.clone();
+        let mut s = *start;
+        let mut moves = Vec::new();
+        if !goal.is_reachable(start) {
+            return Solution::unsolved(0);
+        }
+        if let Goal::Exact(target) = goal.resolve(start) {
+            moves = placement_moves(start, &target);
+        } else {
+            while !s.iscomplete() {
+                let mv = policy.next_move(&s);
+                mv.apply(&mut s);
+                moves.push(mv);
+            }
+        }
+        let status = if moves.is_empty() { Status::Optimal } else { Status::Solved };
+        let nodes_expanded = moves.len() as u64;
+        return Solution { moves, status, nodes_expanded };
+    }
+}
+
+// Mean solution length over `corpus`; unsolved stacks are left out
+pub fn average_moves<S : Solver + ?Sized>(solver : &S, corpus : &[SliceStack]) -> f64 {
+    let mut total = 0;
+    let mut solved = 0;
+    for s in corpus.iter() {
+        let solution = solver.solve(s);
+        if solution.is_solved() {
+            total += solution.len();
+            solved += 1;
+        }
+    }
+    if solved == 0 {
+        return 0.0;
+    }
+    return total as f64 / solved as f64;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use logic::testing::*;
+    use solver::GreedySolver;
+
+    fn check<P : Policy>(policy : P, corpus : &[SliceStack]) -> f64 {
+        let solver = PolicyGreedy::new(policy);
+        for s in corpus.iter() {
+            let solution = solver.solve(s);
+            assert!(solution.apply(s).iscomplete());
+            assert_eq!(solution, solver.solve(s));
+        }
+        return average_moves(&solver, corpus);
+    }
+
+    #[test]
+    fn policy_greedy_test() {
+        let ss = random_stacks(&mut PrngCtxGaloisLsfw::new(34), 200, MAX_SLICES as i32, 8);
+        // LeftFirst is the original greedy loop
+        let greedy = GreedySolver::default();
+        for s in ss.iter() {
+            assert_eq!(greedy.solve(s), PolicyGreedy::new(LeftFirst).solve(s));
+        }
+        let left = check(LeftFirst, &ss);
+        check(RightFirst, &ss);
+        check(Alternating::new(), &ss);
+        check(SeededRandom::new(7), &ss);
+        let lookahead = check(Lookahead::new(1), &ss[0..50]);
+        assert!(lookahead <= average_moves(&greedy, &ss[0..50]));
+        assert!(left > 0.0);
+    }
+
+    #[test]
+    fn lookahead_never_longer_test() {
+        let greedy = PolicyGreedy::new(LeftFirst);
+        let lookahead = PolicyGreedy::new(Lookahead::new(2));
+        for s in random_stacks(&mut PrngCtxGaloisLsfw::new(34), 5, MAX_SLICES as i32, 8).iter() {
+            assert!(lookahead.solve(s).len() <= greedy.solve(s).len());
+        }
+    }
+}
+
+/* vim: set ts=4 sts=4 sw=4 et : */
@@ -0,0 +1,266 @@
+/*
+    Copyright (C) 2016  Erik Beran
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+// Every shortest solution of one stack, for telling puzzles with a single
+// answer from ones with many.
+
+use logic::*;
+use solver::{lower_bound, IdaSolver, Solver};
+use std::collections::HashMap;
+
+// Which optimal move sequences count as different
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Equivalence {
+    // Every sequence of moves. Two different flips never give the same stack,
+    // so this is also every sequence of states.
+    Sequences,
+    // Flips on separate parts of the stack (Left i and Right j with i < j)
+    // reach the same state in either order. Sequences that differ only by
+    // swapping such neighbouring flips count once, as the one that never has
+    // Right j directly before Left i.
+    Reorderings,
+}
+
+// Counts of (state, moves left, smallest Left index allowed next) -> number
+// of sequences of exactly that many moves that complete the state
+type Memo<const N : usize> = HashMap<(SliceStackN<N>, usize, i32), u64>;
+
+// The optimal solutions of one stack. The optimal length comes from
+// IdaSolver; counting is then a depth-first search bounded by lower_bound
+// with the counts memoized, so stacks reached along many paths are only
+// explored once. Counts saturate at u64::MAX.
+pub struct OptimalSolutions<const N : usize = MAX_SLICES> {
+    start : SliceStackN<N>,
+    length : usize,
+    equivalence : Equivalence,
+    memo : Memo<N>,
+}
+
+impl<const N : usize> OptimalSolutions<N> {
+    pub fn new<T>(start : &SliceStackN<N, T>, equivalence : Equivalence)
+        -> OptimalSolutions<N> {
+        let start = start.without_objects();
+        let length = IdaSolver::new().solve(&start).len();
+        OptimalSolutions { start, length, equivalence, memo:HashMap::new() }
+    }
+
+    // Moves in each optimal solution
+    pub fn length(&self) -> usize { self.length }
+
+    pub fn count(&mut self) -> u64 {
+        let start = self.start;
+        return self.count_from(&start, self.length, 0);
+    }
+
+    pub fn is_unique(&mut self) -> bool { self.count() == 1 }
+
+    // The solutions one at a time, in Move::all order; only the moves of the
+    // current one are kept besides the memo
+    pub fn iter(&mut self) -> Solutions<'_, N> {
+        let start = self.start;
+        let mut stack = Vec::new();
+        let mut pending_empty = false;
+        if self.length == 0 {
+            pending_empty = true;
+        } else {
+            let children = self.children(&start, self.length, 0);
+            stack.push(Frame { children, next:0 });
+        }
+        Solutions { solutions:self, stack, path:Vec::new(), pending_empty }
+    }
+
+    // Sequences of exactly `remaining` moves completing `s` whose first move
+    // is no Left below `min_left`
+    fn count_from(&mut self, s : &SliceStackN<N>, remaining : usize, min_left : i32) -> u64 {
+        if remaining == 0 {
+            return s.iscomplete() as u64;
+        }
+        if lower_bound(s) > remaining as i32 {
+            return 0;
+        }
+        let key = (*s, remaining, min_left);
+        if let Some(&count) = self.memo.get(&key) {
+            return count;
+        }
+        let mut count : u64 = 0;
+        for mv in Move::all(s.count()) {
+            if mv.side == Side::Left && mv.index < min_left {
+                continue;
+            }
+            let mut next = *s;
+            mv.apply(&mut next);
+            let next_min_left = self.next_min_left(mv);
+            count = count.saturating_add(self.count_from(&next, remaining - 1, next_min_left));
+        }
+        self.memo.insert(key, count);
+        return count;
+    }
+
+    fn next_min_left(&self, mv : Move) -> i32 {
+        match (self.equivalence, mv.side) {
+            (Equivalence::Reorderings, Side::Right) => return mv.index,
+            _ => return 0,
+        }
+    }
+
+    // Moves from `s` that start at least one counted solution
+    fn children(&mut self, s : &SliceStackN<N>, remaining : usize, min_left : i32)
+        -> Vec<(Move, SliceStackN<N>, i32)> {
+        let mut children = Vec::new();
+        for mv in Move::all(s.count()) {
+            if mv.side == Side::Left && mv.index < min_left {
+                continue;
+            }
+            let mut next = *s;
+            mv.apply(&mut next);
+            let next_min_left = self.next_min_left(mv);
+            if self.count_from(&next, remaining - 1, next_min_left) > 0 {
+                children.push((mv, next, next_min_left));
+            }
+        }
+        return children;
+    }
+}
+
+struct Frame<const N : usize> {
+    children : Vec<(Move, SliceStackN<N>, i32)>,
+    next : usize,
+}
+
+// Iterator from OptimalSolutions::iter
+pub struct Solutions<'a, const N : usize> {
+    solutions : &'a mut OptimalSolutions<N>,
+    // One frame per move in `path`, plus the one being chosen from
+    stack : Vec<Frame<N>>,
+    path : Vec<Move>,
+    // The start is already complete; its one solution has no moves
+    pending_empty : bool,
+}
+
+impl<'a, const N : usize> Iterator for Solutions<'a, N> {
+    type Item = Vec<Move>;
+
+    fn next(&mut self) -> Option<Vec<Move>> {
+        if self.pending_empty {
+            self.pending_empty = false;
+            return Some(Vec::new());
+        }
+        loop {
+            let depth = self.stack.len();
+            let (mv, next, min_left) = {
+                let frame = self.stack.last_mut()?;
+                if frame.next == frame.children.len() {
+                    self.stack.pop();
+                    continue;
+                }
+                frame.next += 1;
+                frame.children[frame.next - 1]
+            };
+            // Drop the moves of the branch just finished
+            self.path.truncate(depth - 1);
+            self.path.push(mv);
+            let remaining = self.solutions.length - depth;
+            if remaining == 0 {
+                return Some(self.path.clone());
+            }
+            let children = self.solutions.children(&next, remaining, min_left);
+            self.stack.push(Frame { children, next:0 });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use logic::testing::*;
+    use prng::*;
+
+    // Every sequence of `length` moves that completes `s`
+    fn brute_force(s : &SliceStack, length : usize, path : &mut Vec<Move>,
+                   found : &mut Vec<Vec<Move>>) {
+        if path.len() == length {
+            if s.iscomplete() {
+                found.push(path.clone());
+            }
+            return;
+        }
+        for mv in Move::all(s.count()) {
+            let mut next = *s;
+            mv.apply(&mut next);
+            path.push(mv);
+            brute_force(&next, length, path, found);
+            path.pop();
+        }
+    }
+
+    #[test]
+    fn solutions_known_test() {
+        let mut done = OptimalSolutions::new(&stack("AABBC"), Equivalence::Sequences);
+        assert_eq!(0, done.length());
+        assert_eq!(1, done.count());
+        assert_eq!(vec![Vec::<Move>::new()], done.iter().collect::<Vec<_>>());
+
+        // L1 gives BAA, R1 gives AAB
+        let mut s = OptimalSolutions::new(&stack("ABA"), Equivalence::Sequences);
+        assert_eq!(1, s.length());
+        assert_eq!(2, s.count());
+        assert!(!s.is_unique());
+        let all : Vec<Vec<Move>> = s.iter().collect();
+        assert_eq!(vec![vec![Move::new(1, Side::Left)], vec![Move::new(1, Side::Right)]], all);
+    }
+
+    #[test]
+    fn solutions_match_brute_force_test() {
+        for s in random_stacks(&mut PrngCtxSplitMix64::new(24), 40, 7, 3) {
+            let mut solutions = OptimalSolutions::new(&s, Equivalence::Sequences);
+            let mut expected = Vec::new();
+            brute_force(&s, solutions.length(), &mut Vec::new(), &mut expected);
+            assert_eq!(expected.len() as u64, solutions.count());
+            let listed : Vec<Vec<Move>> = solutions.iter().collect();
+            assert_eq!(expected, listed);
+        }
+    }
+
+    #[test]
+    fn solutions_reorderings_test() {
+        let mut merged_any = false;
+        for s in random_stacks(&mut PrngCtxSplitMix64::new(25), 40, 9, 4) {
+            let mut all = OptimalSolutions::new(&s, Equivalence::Sequences);
+            let mut reordered = OptimalSolutions::new(&s, Equivalence::Reorderings);
+            let listed : Vec<Vec<Move>> = reordered.iter().collect();
+            assert_eq!(listed.len() as u64, reordered.count());
+            assert!(reordered.count() >= 1);
+            assert!(reordered.count() <= all.count());
+            merged_any |= reordered.count() < all.count();
+            for moves in listed.iter() {
+                assert_eq!(all.length(), moves.len());
+                let mut end = s;
+                for mv in moves.iter() {
+                    mv.apply(&mut end);
+                }
+                assert!(end.iscomplete());
+                for w in moves.windows(2) {
+                    assert!(!(w[0].side == Side::Right && w[1].side == Side::Left
+                              && w[1].index < w[0].index));
+                }
+            }
+        }
+        assert!(merged_any);
+    }
+}
+
+/* vim: set ts=4 sts=4 sw=4 et : */
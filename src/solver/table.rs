@@ -0,0 +1,348 @@
+/*
+    Copyright (C) 2016  Erik Beran
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+// Precomputed distances for one (count, type_count) configuration.
+//
+// States are relabel classes indexed by rank_relabeled(), which covers every
+// stack of `count` slices with at most type_count colors. Each state stores
+// its distance to a complete stack modulo 3 in 2 bits. A flip changes the
+// distance by at most one, so from a stack at distance d the neighbours are
+// at d - 1, d or d + 1, and the one whose code is one below (mod 3) is always
+// a step closer; that's all a solver needs.
+
+use logic::*;
+use solver::{Goal, IdaSolver, Limits, Solution, Solver, Status};
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+
+const MAGIC : &[u8; 4] = b"SGDT";
+const BITS_PER_STATE : usize = 2;
+const STATES_PER_BYTE : usize = 8 / BITS_PER_STATE;
+// Marks states the search hasn't reached while building
+const UNKNOWN : u8 = u8::MAX;
+
+// Tables only exist for sizes whose states fit a u64, so every stack a table
+// covers has a rank
+fn rank<const N : usize>(s : &SliceStackN<N>, type_count : i32) -> u64 {
+    return s.rank_relabeled_in(type_count).unwrap();
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DistanceTable {
+    count : i32,
+    type_count : i32,
+    states : u64,
+    // Distance mod 3 per state, four states per byte, lowest bits first
+    codes : Vec<u8>,
+}
+
+impl DistanceTable {
+    // Retrograde analysis: breadth-first from every complete state, flips
+    // being their own inverse. Needs a byte per state while building.
+    pub fn build(count : i32, type_count : i32) -> Result<DistanceTable, SliceStackError> {
+        if count as usize > MAX_SLICES {
+            return Err(SliceStackError::TooManySlices { count:count as usize,
+                capacity:MAX_SLICES });
+        }
+        let states = match relabeled_state_count(count, type_count) {
+            Some(states) => states,
+            None => return Err(SliceStackError::TooManyStates { count, type_count }),
+        };
+        let mut distance = vec![UNKNOWN; states as usize];
+        let mut frontier : Vec<u64> = Vec::new();
+        for r in 0..states {
+            if SliceStack::unrank_relabeled(r, count, type_count)?.iscomplete() {
+                distance[r as usize] = 0;
+                frontier.push(r);
+            }
+        }
+
+        let mut d : u8 = 0;
+        while !frontier.is_empty() {
+            d += 1;
+            let mut next_frontier = Vec::new();
+            for r in frontier.iter() {
+                let s = SliceStack::unrank_relabeled(*r, count, type_count)?;
+                for mv in Move::all(count) {
+                    let mut next = s;
+                    mv.apply(&mut next);
+                    let nr = rank(&next, type_count);
+                    if distance[nr as usize] == UNKNOWN {
+                        distance[nr as usize] = d;
+                        next_frontier.push(nr);
+                    }
+                }
+            }
+            frontier = next_frontier;
+        }
+
+        let mut table = DistanceTable { count, type_count, states,
+            codes:vec![0; (states as usize).div_ceil(STATES_PER_BYTE)] };
+        for (r, d) in distance.iter().enumerate() {
+            table.set_code(r as u64, d % 3);
+        }
+        return Ok(table);
+    }
+
+    pub fn count(&self) -> i32 { self.count }
+
+    pub fn type_count(&self) -> i32 { self.type_count }
+
+    // Number of relabel classes stored
+    pub fn states(&self) -> u64 { self.states }
+
+    // Whether `s` is one of the stacks this table covers: the same count and
+    // no more colors in use, whatever type_count it was declared with
+    pub fn covers<const N : usize>(&self, s : &SliceStackN<N>) -> bool {
+        return s.count() == self.count && s.colors().len() as i32 <= self.type_count;
+    }
+
+    fn code(&self, rank : u64) -> u8 {
+        let i = rank as usize;
+        let shift = (i % STATES_PER_BYTE) * BITS_PER_STATE;
+        return (self.codes[i / STATES_PER_BYTE] >> shift) & 0b11;
+    }
+
+    fn set_code(&mut self, rank : u64, code : u8) {
+        let i = rank as usize;
+        let shift = (i % STATES_PER_BYTE) * BITS_PER_STATE;
+        let byte = &mut self.codes[i / STATES_PER_BYTE];
+        *byte = (*byte & !(0b11 << shift)) | (code << shift);
+    }
+
+    // A move one step closer to complete; None if `s` is complete or not
+    // covered by the table
+    pub fn best_move<const N : usize>(&self, s : &SliceStackN<N>) -> Option<Move> {
+        if !self.covers(s) || s.iscomplete() {
+            return None;
+        }
+        let closer = (self.code(rank(s, self.type_count)) + 2) % 3;
+        for mv in Move::all(s.count()) {
+            let mut next = s.without_objects();
+            mv.apply(&mut next);
+            if self.code(rank(&next, self.type_count)) == closer {
+                return Some(mv);
+            }
+        }
+        // Only if the table is for different stacks than it claims
+        return None;
+    }
+
+    // Header (magic, count, type_count), then the packed codes. The header
+    // has a byte for each, so larger tables can't be written.
+    pub fn write_to<W : Write>(&self, w : &mut W) -> io::Result<()> {
+        if self.count > u8::MAX as i32 || self.type_count > u8::MAX as i32 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                format!("can't store a table for {} slices of {} colors",
+                        self.count, self.type_count)));
+        }
+        w.write_all(MAGIC)?;
+        w.write_all(&[self.count as u8, self.type_count as u8])?;
+        return w.write_all(&self.codes);
+    }
+
+    pub fn read_from<R : Read>(mut r : R) -> io::Result<DistanceTable> {
+        let mut header = [0u8; 6];
+        r.read_exact(&mut header)?;
+        if &header[0..4] != MAGIC {
+            return Err(invalid_data("not a distance table".to_string()));
+        }
+        let count = header[4] as i32;
+        let type_count = header[5] as i32;
+        if count as usize > MAX_SLICES {
+            return Err(invalid_data(format!("table is for {} slices", count)));
+        }
+        // build() never makes a table without states
+        let states = match relabeled_state_count(count, type_count) {
+            Some(states) if states > 0 => states,
+            _ => return Err(invalid_data(format!("no table for {} slices of {} colors",
+                                                 count, type_count))),
+        };
+        let mut codes = Vec::new();
+        r.read_to_end(&mut codes)?;
+        if codes.len() != (states as usize).div_ceil(STATES_PER_BYTE) {
+            return Err(invalid_data(format!("expected {} states, found {} bytes",
+                                            states, codes.len())));
+        }
+        return Ok(DistanceTable { count, type_count, states, codes });
+    }
+
+    pub fn save<P : AsRef<Path>>(&self, path : P) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write_to(&mut w)?;
+        return w.flush();
+    }
+
+    pub fn load<P : AsRef<Path>>(path : P) -> io::Result<DistanceTable> {
+        return DistanceTable::read_from(io::BufReader::new(File::open(path)?));
+    }
+}
+
+fn invalid_data(message : String) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, message);
+}
+
+// Optimal moves straight from a DistanceTable, no search. Stacks the table
+// doesn't cover come back Status::Unsolved, and so do stacks a damaged table
+// can't lead to complete. The table only knows about
+// complete stacks, so other goals than Goal::Any go to IdaSolver instead.
+#[derive(Debug, Clone)]
+pub struct TableSolver {
+    pub table : DistanceTable,
+}
+
+impl TableSolver {
+    pub fn new(table : DistanceTable) -> TableSolver { TableSolver { table } }
+
+    pub fn load<P : AsRef<Path>>(path : P) -> io::Result<TableSolver> {
+        return Ok(TableSolver::new(DistanceTable::load(path)?));
+    }
+}
+
+impl<const N : usize> Solver<N> for TableSolver {
+    fn solve_goal(&self, start : &SliceStackN<N>, goal : &Goal, limits : &Limits) -> Solution {
+        if !goal.is_any() {
+            return IdaSolver::new().solve_goal(start, goal, limits);
+        }
+        if !self.table.covers(start) {
+            return Solution::unsolved(0);
+        }
+        let mut s = start.without_objects();
+        let mut moves = Vec::new();
+        // Every stack can be put together in fewer than 2 * count flips (see
+        // placement_moves); a corrupt table could send the walk in circles
+        let max_moves = 2 * s.count() as usize;
+        while moves.len() < max_moves && !limits.exceeded(moves.len() as u64) {
+            match self.table.best_move(&s) {
+                Some(mv) => {
+                    mv.apply(&mut s);
+                    moves.push(mv);
+                }
+                None => break,
+            }
+        }
+        let nodes_expanded = moves.len() as u64;
+        // Either out of budget or a table that doesn't match its header
+        if !s.iscomplete() {
+            return Solution::unsolved(nodes_expanded);
+        }
+        return Solution { moves, status:Status::Optimal, nodes_expanded };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use logic::testing::*;
+    use prng::*;
+
+    #[test]
+    fn table_matches_ida_test() {
+        let table = DistanceTable::build(10, 4).unwrap();
+        // 2 bits for each of the 43947 classes
+        assert_eq!(43947, table.states());
+        assert_eq!(10987, table.codes.len());
+        let solver = TableSolver::new(table);
+        let ida = IdaSolver::new();
+        let mut rng = PrngCtxSplitMix64::new(17);
+        for s in random_stacks(&mut rng, 100, 10, 4) {
+            let solution = solver.solve(&s);
+            assert!(solution.is_optimal());
+            assert!(solution.apply(&s).iscomplete());
+            assert_eq!(ida.solve(&s).len(), solution.len());
+        }
+        // Fewer colors are in the table too
+        for s in random_stacks(&mut rng, 50, 10, 3) {
+            let solution = solver.solve(&s);
+            assert!(solution.is_optimal());
+            assert!(solution.apply(&s).iscomplete());
+            assert_eq!(ida.solve(&s).len(), solution.len());
+        }
+        // Only the colors in use count
+        let s = stack("ABCABCABCA/6");
+        assert!(solver.table.covers(&s));
+        assert_eq!(ida.solve(&s).len(), solver.solve(&s).len());
+        assert!(!solver.table.covers(&stack("ABCDEABCDE")));
+        let other : SliceStack = "ABCBD".parse().unwrap();
+        assert!(!solver.solve(&other).is_solved());
+        assert_eq!(None, solver.table.best_move(&other));
+    }
+
+    #[test]
+    fn table_file_test() {
+        let table = DistanceTable::build(7, 3).unwrap();
+        let mut bytes = Vec::new();
+        table.write_to(&mut bytes).unwrap();
+        assert_eq!(b"SGDT\x07\x03", &bytes[0..6]);
+        assert_eq!(table, DistanceTable::read_from(&bytes[..]).unwrap());
+
+        let short = &bytes[0..bytes.len() - 1];
+        assert_eq!(io::ErrorKind::InvalidData, DistanceTable::read_from(short).unwrap_err().kind());
+        assert!(DistanceTable::read_from(&b"SGDX\x07\x03"[..]).is_err());
+        // No 7 slice stack has no colors
+        assert_eq!(io::ErrorKind::InvalidData,
+                   DistanceTable::read_from(&b"SGDT\x07\x00"[..]).unwrap_err().kind());
+        let wide = DistanceTable { count:1, type_count:300, states:1, codes:vec![0] };
+        assert_eq!(io::ErrorKind::InvalidInput, wide.write_to(&mut Vec::new()).unwrap_err().kind());
+
+        let path = std::env::temp_dir().join(format!("sortgame-table-{}", std::process::id()));
+        table.save(&path).unwrap();
+        let solver = TableSolver::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let s : SliceStack = "ABCABCA".parse().unwrap();
+        assert!(solver.solve(&s).apply(&s).iscomplete());
+    }
+
+    #[test]
+    fn table_corrupt_test() {
+        let table = DistanceTable::build(7, 3).unwrap();
+        let mut bytes = Vec::new();
+        table.write_to(&mut bytes).unwrap();
+        let s : SliceStack = "ABCABCA".parse().unwrap();
+        assert!(!TableSolver::new(table.clone()).solve_with_limits(&s, &Limits::none()
+            .with_max_nodes(1)).is_solved());
+
+        // Every state at distance 0: no move is ever closer
+        let mut zeroed = bytes.clone();
+        for b in zeroed[6..].iter_mut() {
+            *b = 0;
+        }
+        let solver = TableSolver::new(DistanceTable::read_from(&zeroed[..]).unwrap());
+        assert!(!solver.solve(&s).is_solved());
+
+        // Random codes may lead anywhere, including in circles, but the walk
+        // still ends and only claims stacks it completed
+        let mut rng = PrngCtxSplitMix64::new(170);
+        for _ in 0..20 {
+            let mut garbled = bytes.clone();
+            for b in garbled[6..].iter_mut() {
+                *b = rng.next_u32() as u8;
+            }
+            let solver = TableSolver::new(DistanceTable::read_from(&garbled[..]).unwrap());
+            for t in random_stacks(&mut rng, 10, 7, 3) {
+                let solution = solver.solve(&t);
+                assert!(solution.len() <= 2 * 7);
+                if solution.is_solved() {
+                    assert!(solution.apply(&t).iscomplete());
+                }
+            }
+        }
+    }
+}
+
+/* vim: set ts=4 sts=4 sw=4 et : */
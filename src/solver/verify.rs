@@ -0,0 +1,285 @@
+/*
+    Copyright (C) 2016  Erik Beran
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+// Checking move lists from outside the solver: front-ends, new solvers and
+// replay files sent in by players.
+
+use logic::*;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+// A move list that was replayed and ends on a complete stack
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedSolution<const N : usize = MAX_SLICES> {
+    pub start : SliceStackN<N>,
+    pub moves : Vec<Move>,
+    pub end : SliceStackN<N>,
+}
+
+impl<const N : usize> VerifiedSolution<N> {
+    pub fn len(&self) -> usize { self.moves.len() }
+
+    pub fn is_empty(&self) -> bool { self.moves.is_empty() }
+}
+
+// Steps are numbered from 0, in the order the moves were given
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyError<const N : usize = MAX_SLICES> {
+    // The flip index is outside the stack; the move wasn't applied
+    OutOfRange { step : usize, mv : Move, before : SliceStackN<N> },
+    // The flip left the stack as it was
+    NoOp { step : usize, mv : Move, before : SliceStackN<N> },
+    // Every move was fine but the last one doesn't complete the stack. With
+    // no moves at all, step is None and before and after are the start.
+    Incomplete { step : Option<usize>, before : SliceStackN<N>, after : SliceStackN<N> },
+}
+
+impl<const N : usize> VerifyError<N> {
+    // Index of the move that failed, if any
+    pub fn step(&self) -> Option<usize> {
+        match *self {
+            VerifyError::OutOfRange { step, .. } | VerifyError::NoOp { step, .. } => Some(step),
+            VerifyError::Incomplete { step, .. } => step,
+        }
+    }
+
+    // Stack before the failing step
+    pub fn before(&self) -> &SliceStackN<N> {
+        match *self {
+            VerifyError::OutOfRange { ref before, .. }
+            | VerifyError::NoOp { ref before, .. }
+            | VerifyError::Incomplete { ref before, .. } => before,
+        }
+    }
+
+    // Stack after the failing step; the same as before() when the move
+    // couldn't be applied or changed nothing
+    pub fn after(&self) -> &SliceStackN<N> {
+        match *self {
+            VerifyError::Incomplete { ref after, .. } => after,
+            _ => self.before(),
+        }
+    }
+}
+
+impl<const N : usize> fmt::Display for VerifyError<N> {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            VerifyError::OutOfRange { step, mv, ref before } =>
+                write!(f, "step {} ({}): index out of range for {} slices, stack {}",
+                       step, mv, before.count(), before),
+            VerifyError::NoOp { step, mv, ref before } =>
+                write!(f, "step {} ({}) doesn't change the stack: {} -> {}",
+                       step, mv, before, before),
+            VerifyError::Incomplete { step:Some(step), ref before, ref after } =>
+                write!(f, "stack is not complete after the last step {}: {} -> {}",
+                       step, before, after),
+            VerifyError::Incomplete { step:None, ref after, .. } =>
+                write!(f, "no moves given and the stack {} is not complete", after),
+        }
+    }
+}
+
+impl<const N : usize> Error for VerifyError<N> {}
+
+// Replays `moves` on `start`, stopping at the first move that is out of
+// range or changes nothing, and requires the result to be complete
+pub fn verify<const N : usize>(start : &SliceStackN<N>, moves : &[Move])
+    -> Result<VerifiedSolution<N>, VerifyError<N>> {
+    let mut before = *start;
+    let mut s = *start;
+    for (step, mv) in moves.iter().enumerate() {
+        if !mv.is_legal(s.count()) {
+            return Err(VerifyError::OutOfRange { step, mv:*mv, before:s });
+        }
+        before = s;
+        mv.apply(&mut s);
+        if s == before {
+            return Err(VerifyError::NoOp { step, mv:*mv, before });
+        }
+    }
+    if !s.iscomplete() {
+        let step = if moves.is_empty() { None } else { Some(moves.len() - 1) };
+        return Err(VerifyError::Incomplete { step, before, after:s });
+    }
+    return Ok(VerifiedSolution { start:*start, moves:moves.to_vec(), end:s });
+}
+
+// A player's game as text: the start stack in stack notation on the first
+// line, then the moves in move notation separated by spaces, commas or new
+// lines. Everything after a '#' on a line is a comment.
+//     # level 12
+//     ABCBD
+//     L1 L2
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replay<const N : usize = MAX_SLICES> {
+    pub start : SliceStackN<N>,
+    pub moves : Vec<Move>,
+}
+
+// Lines are numbered from 1, as in an editor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    // Nothing but comments and blank lines
+    Empty,
+    Stack { line : usize, error : ParseStackError },
+    Move { line : usize, error : ParseMoveError },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReplayError::Empty => write!(f, "replay has no start stack"),
+            ReplayError::Stack { line, ref error } => write!(f, "line {}: {}", line, error),
+            ReplayError::Move { line, ref error } => write!(f, "line {}: {}", line, error),
+        }
+    }
+}
+
+impl Error for ReplayError {}
+
+impl<const N : usize> Replay<N> {
+    pub fn verify(&self) -> Result<VerifiedSolution<N>, VerifyError<N>> {
+        return verify(&self.start, &self.moves);
+    }
+}
+
+impl<const N : usize> fmt::Display for Replay<N> {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.start)?;
+        for (i, mv) in self.moves.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", mv)?;
+        }
+        return writeln!(f);
+    }
+}
+
+impl<const N : usize> FromStr for Replay<N> {
+    type Err = ReplayError;
+
+    fn from_str(text : &str) -> Result<Replay<N>, ReplayError> {
+        let mut start : Option<SliceStackN<N>> = None;
+        let mut moves = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            if start.is_none() {
+                start = Some(line.parse().map_err(|error| ReplayError::Stack { line:i + 1, error })?);
+                continue;
+            }
+            for word in line.split(|c : char| c == ',' || c.is_whitespace()) {
+                if word.is_empty() {
+                    continue;
+                }
+                moves.push(word.parse().map_err(|error| ReplayError::Move { line:i + 1, error })?);
+            }
+        }
+        match start {
+            Some(start) => return Ok(Replay { start, moves }),
+            None => return Err(ReplayError::Empty),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use logic::testing::*;
+    use prng::*;
+    use solver::*;
+
+    #[test]
+    fn verify_solver_output_test() {
+        let greedy = GreedySolver::default();
+        for s in random_stacks(&mut PrngCtxSplitMix64::new(19), 100, MAX_SLICES as i32, 6) {
+            let solution = greedy.solve(&s);
+            let verified = verify(&s, &solution.moves).unwrap();
+            assert_eq!(solution.len(), verified.len());
+            assert_eq!(solution.apply(&s), verified.end);
+        }
+        assert!(verify(&stack("AAB"), &[]).unwrap().is_empty());
+
+        // Past MAX_SLICES
+        let mut rng = PrngCtxSplitMix64::new(24);
+        let mut s = SliceStackN::<24>::new();
+        s.init_with(&mut rng, 24, 8);
+        let solution = greedy.solve(&s);
+        let verified = verify(&s, &solution.moves).unwrap();
+        assert_eq!(solution.apply(&s), verified.end);
+        let replay = Replay { start:s, moves:solution.moves };
+        let text = replay.to_string();
+        assert_eq!(replay, text.parse::<Replay<24>>().unwrap());
+        assert_eq!(verified, replay.verify().unwrap());
+    }
+
+    #[test]
+    fn verify_errors_test() {
+        let s = stack("ABCBD");
+        let l1 = Move::new(1, Side::Left);
+        let l2 = Move::new(2, Side::Left);
+
+        let e = verify(&s, &[l1, Move::new(5, Side::Right)]).unwrap_err();
+        assert_eq!(VerifyError::OutOfRange { step:1, mv:Move::new(5, Side::Right),
+                                             before:stack("BACBD") }, e);
+        assert_eq!("step 1 (R5): index out of range for 5 slices, stack BACBD", e.to_string());
+
+        // Reversing A,B,A gives the same stack
+        let e = verify(&stack("ABACD"), &[l2]).unwrap_err();
+        assert_eq!(Some(0), e.step());
+        assert_eq!("step 0 (L2) doesn't change the stack: ABACD -> ABACD", e.to_string());
+        let e = verify(&s, &[Move::new(0, Side::Left)]).unwrap_err();
+        assert_eq!(&s, e.after());
+
+        let e = verify(&s, &[l1]).unwrap_err();
+        assert_eq!(VerifyError::Incomplete { step:Some(0), before:s, after:stack("BACBD") }, e);
+        assert_eq!("stack is not complete after the last step 0: ABCBD -> BACBD", e.to_string());
+        let e = verify(&s, &[]).unwrap_err();
+        assert_eq!(None, e.step());
+        assert_eq!(&s, e.before());
+
+        let verified = verify(&s, &[l1, l2]).unwrap();
+        assert_eq!(stack("CABBD"), verified.end);
+    }
+
+    #[test]
+    fn replay_test() {
+        let text = "# level 12\nABCBD\nL1, L2 # joins the B's\n\n";
+        let replay : Replay = text.parse().unwrap();
+        assert_eq!(stack("ABCBD"), replay.start);
+        assert_eq!(vec![Move::new(1, Side::Left), Move::new(2, Side::Left)], replay.moves);
+        assert_eq!(2, replay.verify().unwrap().len());
+        assert_eq!(replay, replay.to_string().parse().unwrap());
+
+        assert_eq!(Err(ReplayError::Empty), "# nothing\n".parse::<Replay>());
+        assert_eq!(Err(ReplayError::Move { line:3, error:ParseMoveError("X1".to_string()) }),
+                   "ABA\nL1\nX1".parse::<Replay>());
+        match "ABxA\nL1".parse::<Replay>() {
+            Err(ReplayError::Stack { line:1, .. }) => {}
+            other => panic!("{:?}", other),
+        }
+        let replay : Replay = "ABCBD\nL1".parse().unwrap();
+        assert_eq!(Some(0), replay.verify().unwrap_err().step());
+    }
+}
+
+/* vim: set ts=4 sts=4 sw=4 et : */